@@ -0,0 +1,389 @@
+//! Writing worlds directly into a Java Edition save's Anvil region files
+
+use crate::block_state::BlockState;
+use crate::error::{Error, Result};
+use crate::world::{map, World, MC_DATA_VERSION};
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The build height of 1.17 in sections, which is 0 to 255 in blocks
+const SECTIONS: std::ops::Range<i32> = 0..16;
+
+const SECTOR_SIZE: usize = 4096;
+
+impl World {
+    /// Places the world into the save at `world_dir` with its origin at `x`, `y`, `z`.
+    /// Chunks that don't exist yet are created, and existing chunks have the blocks and block
+    /// entities inside of the world's bounds replaced. Touched chunks are marked to have their
+    /// lighting recalculated. Only chunks saved by the same version as the schematics are
+    /// updated, since older chunks need upgrades the game won't run once they're stamped with
+    /// the new version. Fails with `Error::OutsideBuildHeight` if any of the world would be
+    /// below y=0 or above y=255.
+    pub fn save_to_region(&self, world_dir: &str, x: i32, y: i32, z: i32) -> Result<()> {
+        let (sx, sy, sz) = self.size();
+        if sx == 0 || sy == 0 || sz == 0 {
+            return Ok(());
+        }
+        let min = self.min();
+        // The lowest corner of the world in the save
+        let (x, y, z) = (x + min.x, y + min.y, z + min.z);
+        let (ex, ey, ez) = (x + sx as i32, y + sy as i32, z + sz as i32);
+        // The game throws away sections outside of the build height
+        for y in [y, ey - 1] {
+            if !SECTIONS.contains(&(y >> 4)) {
+                return Err(Error::OutsideBuildHeight { y });
+            }
+        }
+        let palette = self.palette();
+        let block_entities = self.block_entities().collect::<Vec<_>>();
+
+        let region_dir = Path::new(world_dir).join("region");
+        fs::create_dir_all(&region_dir)?;
+        let mut regions = HashMap::new();
+
+        for cx in (x >> 4)..=((ex - 1) >> 4) {
+            for cz in (z >> 4)..=((ez - 1) >> 4) {
+                let region = match regions.entry((cx >> 5, cz >> 5)) {
+                    Entry::Occupied(entry) => entry.into_mut(),
+                    Entry::Vacant(entry) => {
+                        let file_name = format!("r.{}.{}.mca", cx >> 5, cz >> 5);
+                        entry.insert(RegionFile::load(region_dir.join(file_name))?)
+                    }
+                };
+                let chunk_idx = ((cx & 31) + (cz & 31) * 32) as usize;
+
+                let mut level = match region.read_chunk(chunk_idx)? {
+                    Some(chunk) => {
+                        let data_version = match chunk.get("DataVersion") {
+                            Some(&nbt::Value::Int(data_version)) => data_version,
+                            _ => 0,
+                        };
+                        if data_version != MC_DATA_VERSION {
+                            return Err(Error::InvalidChunk(format!(
+                                "chunk {}, {} has unsupported data version {}",
+                                cx, cz, data_version
+                            )));
+                        }
+                        match chunk.get("Level") {
+                            Some(nbt::Value::Compound(level)) => level.clone(),
+                            _ => return Err(Error::MissingTag("Level")),
+                        }
+                    }
+                    None => map! {
+                        "xPos" => nbt::Value::Int(cx),
+                        "zPos" => nbt::Value::Int(cz),
+                        "Status" => nbt::Value::String("full".to_owned())
+                    },
+                };
+
+                // The bounds of the world inside of this chunk
+                let (min_x, max_x) = (x.max(cx * 16), ex.min(cx * 16 + 16));
+                let (min_z, max_z) = (z.max(cz * 16), ez.min(cz * 16 + 16));
+                let contains = |bx: i32, by: i32, bz: i32| {
+                    (min_x..max_x).contains(&bx)
+                        && (y..ey).contains(&by)
+                        && (min_z..max_z).contains(&bz)
+                };
+
+                let mut sections = match level.remove("Sections") {
+                    Some(nbt::Value::List(sections)) => sections,
+                    _ => Vec::new(),
+                };
+                for section_y in (y >> 4)..=((ey - 1) >> 4) {
+                    let existing = sections.iter().position(|section| match section {
+                        nbt::Value::Compound(section) => {
+                            section.get("Y") == Some(&nbt::Value::Byte(section_y as i8))
+                        }
+                        _ => false,
+                    });
+                    let mut blocks = match existing {
+                        Some(i) => decode_section(&sections[i])?,
+                        None => vec![BlockState::new("minecraft:air"); 4096],
+                    };
+
+                    for (i, block) in blocks.iter_mut().enumerate() {
+                        let bx = cx * 16 + (i & 15) as i32;
+                        let bz = cz * 16 + ((i >> 4) & 15) as i32;
+                        let by = section_y * 16 + (i >> 8) as i32;
+                        if contains(bx, by, bz) {
+                            let pos = min.offset(bx - x, by - y, bz - z);
+                            block.clone_from(&palette[self.get_block(pos) as usize]);
+                        }
+                    }
+
+                    // Old lighting is dropped along with the section
+                    let section = encode_section(section_y as i8, &blocks);
+                    match existing {
+                        Some(i) => sections[i] = section,
+                        None => sections.push(section),
+                    }
+                }
+                level.insert("Sections".to_owned(), nbt::Value::List(sections));
+
+                let mut tile_entities = match level.remove("TileEntities") {
+                    Some(nbt::Value::List(tile_entities)) => tile_entities,
+                    _ => Vec::new(),
+                };
+                tile_entities.retain(|entity| {
+                    let entity = match entity {
+                        nbt::Value::Compound(entity) => entity,
+                        _ => return false,
+                    };
+                    let coord = |name| match entity.get(name) {
+                        Some(&nbt::Value::Int(coord)) => coord,
+                        _ => 0,
+                    };
+                    !contains(coord("x"), coord("y"), coord("z"))
+                });
+                for &(pos, entity) in &block_entities {
+                    let pos = pos - min;
+                    let (bx, by, bz) = (x + pos.x, y + pos.y, z + pos.z);
+                    if !contains(bx, by, bz) {
+                        continue;
+                    }
+                    let mut entity = entity.to_compound();
+                    entity.insert("x".to_owned(), nbt::Value::Int(bx));
+                    entity.insert("y".to_owned(), nbt::Value::Int(by));
+                    entity.insert("z".to_owned(), nbt::Value::Int(bz));
+                    entity.insert("keepPacked".to_owned(), nbt::Value::Byte(0));
+                    tile_entities.push(nbt::Value::Compound(entity));
+                }
+                level.insert("TileEntities".to_owned(), nbt::Value::List(tile_entities));
+
+                // Make the game recalculate anything that depends on the blocks
+                level.remove("Heightmaps");
+                level.insert("isLightOn".to_owned(), nbt::Value::Byte(0));
+
+                let mut chunk = nbt::Blob::new();
+                chunk.insert("DataVersion", MC_DATA_VERSION)?;
+                chunk.insert("Level", nbt::Value::Compound(level))?;
+                region.write_chunk(chunk_idx, &chunk)?;
+            }
+        }
+
+        for region in regions.values() {
+            region.save()?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads the blocks of a chunk section, indexed by `y * 256 + z * 16 + x`.
+fn decode_section(section: &nbt::Value) -> Result<Vec<BlockState>> {
+    let section = match section {
+        nbt::Value::Compound(section) => section,
+        _ => return Err(Error::MissingTag("Sections")),
+    };
+    // Sections with only lighting have no blocks
+    let palette = match section.get("Palette") {
+        Some(nbt::Value::List(palette)) => palette,
+        _ => return Ok(vec![BlockState::new("minecraft:air"); 4096]),
+    };
+    let palette = palette
+        .iter()
+        .map(|entry| {
+            let entry = match entry {
+                nbt::Value::Compound(entry) => entry,
+                _ => return Err(Error::MissingTag("Palette")),
+            };
+            let name = match entry.get("Name") {
+                Some(nbt::Value::String(name)) => name,
+                _ => return Err(Error::MissingTag("Name")),
+            };
+            let mut state = BlockState::new(name);
+            if let Some(nbt::Value::Compound(properties)) = entry.get("Properties") {
+                for (k, v) in properties {
+                    if let nbt::Value::String(v) = v {
+                        state.set_property(k, v);
+                    }
+                }
+            }
+            Ok(state)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let states = match section.get("BlockStates") {
+        Some(nbt::Value::LongArray(states)) => states,
+        _ => return Err(Error::MissingTag("BlockStates")),
+    };
+
+    let bits = block_state_bits(palette.len());
+    let per_long = 64 / bits;
+    let mask = (1 << bits) - 1;
+    if states.len() < 4096usize.div_ceil(per_long) {
+        return Err(Error::InvalidChunk("block states are too short".to_owned()));
+    }
+    (0..4096)
+        .map(|i| {
+            let idx = (states[i / per_long] as u64 >> (i % per_long * bits)) & mask;
+            palette
+                .get(idx as usize)
+                .cloned()
+                .ok_or_else(|| Error::InvalidChunk(format!("palette index {} is undefined", idx)))
+        })
+        .collect()
+}
+
+fn encode_section(y: i8, blocks: &[BlockState]) -> nbt::Value {
+    let mut palette = Vec::new();
+    let mut ids = HashMap::new();
+    let indices = blocks
+        .iter()
+        .map(|block| {
+            *ids.entry(block).or_insert_with(|| {
+                palette.push(block);
+                palette.len() as u64 - 1
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let bits = block_state_bits(palette.len());
+    let per_long = 64 / bits;
+    let mut states = vec![0i64; 4096usize.div_ceil(per_long)];
+    for (i, idx) in indices.into_iter().enumerate() {
+        states[i / per_long] |= (idx << (i % per_long * bits)) as i64;
+    }
+
+    let palette = palette
+        .into_iter()
+        .map(|block| {
+            let mut entry = HashMap::new();
+            entry.insert("Name".to_owned(), nbt::Value::String(block.name()));
+            if !block.properties.is_empty() {
+                let properties = block
+                    .properties
+                    .iter()
+                    .map(|(k, v)| (k.clone(), nbt::Value::String(v.clone())))
+                    .collect();
+                entry.insert("Properties".to_owned(), nbt::Value::Compound(properties));
+            }
+            nbt::Value::Compound(entry)
+        })
+        .collect();
+
+    nbt::Value::Compound(map! {
+        "Y" => nbt::Value::Byte(y),
+        "Palette" => nbt::Value::List(palette),
+        "BlockStates" => nbt::Value::LongArray(states)
+    })
+}
+
+fn block_state_bits(palette_len: usize) -> usize {
+    ((usize::BITS - (palette_len.max(1) - 1).leading_zeros()) as usize).max(4)
+}
+
+/// A region file holding 32x32 chunks, which are kept compressed until they are needed.
+struct RegionFile {
+    path: PathBuf,
+    /// The compression type and compressed data of each chunk
+    chunks: Vec<Option<(u8, Vec<u8>)>>,
+    timestamps: Vec<u32>,
+}
+
+impl RegionFile {
+    fn load(path: PathBuf) -> Result<RegionFile> {
+        let mut region = RegionFile {
+            path,
+            chunks: vec![None; 1024],
+            timestamps: vec![0; 1024],
+        };
+        if !region.path.exists() {
+            return Ok(region);
+        }
+
+        let mut data = Vec::new();
+        File::open(&region.path)?.read_to_end(&mut data)?;
+        if data.is_empty() {
+            return Ok(region);
+        }
+        if data.len() < 2 * SECTOR_SIZE {
+            return Err(Error::InvalidChunk("region header is truncated".to_owned()));
+        }
+        for i in 0..1024 {
+            let location = u32::from_be_bytes(data[i * 4..i * 4 + 4].try_into().unwrap());
+            let timestamp = &data[SECTOR_SIZE + i * 4..SECTOR_SIZE + i * 4 + 4];
+            region.timestamps[i] = u32::from_be_bytes(timestamp.try_into().unwrap());
+            if location == 0 {
+                continue;
+            }
+
+            let start = (location >> 8) as usize * SECTOR_SIZE;
+            let truncated = || Error::InvalidChunk(format!("chunk {} is truncated", i));
+            let header = data.get(start..start + 5).ok_or_else(truncated)?;
+            let len = u32::from_be_bytes(header[..4].try_into().unwrap()) as usize;
+            let compressed = data
+                .get(start + 5..start + 4 + len)
+                .ok_or_else(truncated)?
+                .to_vec();
+            region.chunks[i] = Some((header[4], compressed));
+        }
+        Ok(region)
+    }
+
+    fn read_chunk(&self, idx: usize) -> Result<Option<nbt::Blob>> {
+        let (compression, data) = match &self.chunks[idx] {
+            Some(chunk) => chunk,
+            None => return Ok(None),
+        };
+        let chunk = match compression {
+            1 => nbt::Blob::from_gzip_reader(&mut &data[..])?,
+            2 => nbt::Blob::from_zlib_reader(&mut &data[..])?,
+            3 => nbt::Blob::from_reader(&mut &data[..])?,
+            _ => {
+                return Err(Error::InvalidChunk(format!(
+                    "unknown compression type {}",
+                    compression
+                )))
+            }
+        };
+        Ok(Some(chunk))
+    }
+
+    fn write_chunk(&mut self, idx: usize, chunk: &nbt::Blob) -> Result<()> {
+        let mut data = Vec::new();
+        chunk.to_zlib_writer(&mut data)?;
+        self.chunks[idx] = Some((2, data));
+        self.timestamps[idx] = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_secs() as u32)
+            .unwrap_or(0);
+        Ok(())
+    }
+
+    fn save(&self) -> Result<()> {
+        let mut locations = Vec::with_capacity(SECTOR_SIZE);
+        let mut timestamps = Vec::with_capacity(SECTOR_SIZE);
+        let mut sectors = Vec::new();
+        for (chunk, timestamp) in self.chunks.iter().zip(&self.timestamps) {
+            let (compression, data) = match chunk {
+                Some(chunk) => chunk,
+                None => {
+                    locations.extend_from_slice(&[0; 4]);
+                    timestamps.extend_from_slice(&[0; 4]);
+                    continue;
+                }
+            };
+            let offset = 2 + sectors.len() / SECTOR_SIZE;
+            sectors.extend_from_slice(&(data.len() as u32 + 1).to_be_bytes());
+            sectors.push(*compression);
+            sectors.extend_from_slice(data);
+            sectors.resize(sectors.len().div_ceil(SECTOR_SIZE) * SECTOR_SIZE, 0);
+            let count = 2 + sectors.len() / SECTOR_SIZE - offset;
+            if count > 255 {
+                return Err(Error::InvalidChunk("chunk is too large".to_owned()));
+            }
+
+            locations.extend_from_slice(&((offset as u32) << 8 | count as u32).to_be_bytes());
+            timestamps.extend_from_slice(&timestamp.to_be_bytes());
+        }
+
+        let mut file = File::create(&self.path)?;
+        file.write_all(&locations)?;
+        file.write_all(&timestamps)?;
+        file.write_all(&sectors)?;
+        Ok(())
+    }
+}
@@ -1,31 +1,307 @@
+use crate::block_state::BlockState;
+use crate::error::{Error, Result};
+use crate::region::Region;
 use crate::world::{BlockDirection, BlockPos, World};
 
-pub fn create_wire(world: &mut World, block: u16, start: BlockPos, end: BlockPos, repeated: bool) {
+/// How `create_wire` and `create_path` account for signal strength.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WireOptions {
+    /// Strength of the signal powering the first wire, like a comparator's output, from 1 to 15
+    pub input: u32,
+    /// Weakest signal the end of the wire needs, from 0 to 15
+    pub min_output: u32,
+    /// Whether to place repeaters to keep the signal from running out
+    pub repeated: bool,
+}
+
+impl Default for WireOptions {
+    fn default() -> Self {
+        Self {
+            input: 15,
+            min_output: 1,
+            repeated: true,
+        }
+    }
+}
+
+/// Draws a straight wire from `start` to `end` on top of `block`, see `create_path`.
+pub fn create_wire(
+    world: &mut World,
+    block: u16,
+    start: BlockPos,
+    end: BlockPos,
+    options: WireOptions,
+) -> Result<Routed> {
+    create_path(world, block, &[start, end], options)
+}
+
+/// Draws a wire on top of `block` through every point in `waypoints`. Points that aren't in a
+/// straight line with the previous one are joined by going along x first, then z. The first
+/// wire is powered at `options.input`, and if `options.repeated` is set, repeaters are placed
+/// wherever the signal would otherwise run out or end up weaker than `options.min_output`.
+/// They never go on a corner, since they can't turn. The output is the last wire or repeater.
+///
+/// Fails with `Error::SignalLost` if repeaters are on but a run of corners is too long for the
+/// signal to make it through, with `Error::SignalTooWeak` if the output ends up below
+/// `options.min_output`, which only happens without repeaters or when the last block is a
+/// corner, and with `Error::InvalidSignalStrength` if `options` holds a strength no signal can
+/// have. Nothing is placed when it fails. Panics if `waypoints` is empty or the waypoints
+/// aren't all at the same height.
+pub fn create_path(
+    world: &mut World,
+    block: u16,
+    waypoints: &[BlockPos],
+    options: WireOptions,
+) -> Result<Routed> {
+    assert!(!waypoints.is_empty(), "paths need at least one waypoint");
+    assert!(
+        waypoints.iter().all(|pos| pos.y == waypoints[0].y),
+        "wires can only be created horizontally"
+    );
+    if !(1..=15).contains(&options.input) {
+        return Err(Error::InvalidSignalStrength(options.input));
+    }
+    if options.min_output > 15 {
+        return Err(Error::InvalidSignalStrength(options.min_output));
+    }
+    let cells = path_cells(waypoints);
+
+    // The direction the signal travels into each block, or `None` for the start
+    let dirs = cells
+        .iter()
+        .enumerate()
+        .map(|(i, &pos)| match i {
+            0 => None,
+            _ => cells[i - 1].direction_to(pos),
+        })
+        .collect::<Vec<_>>();
+    // Repeaters can only go where the signal keeps going straight
+    let straight = |i: usize| i > 0 && (i + 1 == cells.len() || dirs[i] == dirs[i + 1]);
+
+    // Work out where the repeaters go before placing anything
+    let mut ss = options.input as i32;
+    let mut after_repeater = false;
+    let mut repeaters = Vec::new();
+    for (i, &pos) in cells.iter().enumerate() {
+        if i > 0 {
+            // Repeaters always output full strength
+            ss = if after_repeater { 15 } else { ss - 1 };
+        }
+
+        // Place a repeater if the signal wouldn't make it to the next place one can go, or
+        // would be too weak at the end
+        let next_straight = (i + 1..cells.len()).find(|&j| straight(j));
+        let runs_out = match next_straight {
+            Some(j) => (j - i) as i32 > ss,
+            None => ss < options.min_output as i32,
+        };
+        after_repeater = options.repeated && straight(i) && runs_out;
+        // A repeater still works with an empty wire under it, as long as the one before is on
+        if options.repeated && !after_repeater && ss < 1 {
+            return Err(Error::SignalLost { at: pos });
+        }
+        repeaters.push(after_repeater);
+    }
+    let output = if after_repeater { 15 } else { ss.max(0) as u32 };
+    if output < options.min_output {
+        return Err(Error::SignalTooWeak {
+            ss: output,
+            min: options.min_output,
+        });
+    }
+
     let wire = world.add_block("minecraft:redstone_wire");
-    let mut ss = 15;
-    let dir = start.direction_to(end);
-    let repeater_name = match dir {
-        BlockDirection::North => "minecraft:repeater[facing=south]",
-        BlockDirection::South => "minecraft:repeater[facing=north]",
-        BlockDirection::East => "minecraft:repeater[facing=west]",
-        BlockDirection::West => "minecraft:repeater[facing=east]",
-    };
-    let repeater = world.add_block(repeater_name);
-    let mut cur = start;
-    loop {
-        world.set_block(cur, block);
-
-        if ss > 0 || !repeated {
-            world.set_block(cur.offset(0, 1, 0), wire);
+    let mut positions = Vec::new();
+    for (i, &pos) in cells.iter().enumerate() {
+        world.set_block(pos, block);
+        if repeaters[i] {
+            // Repeaters face towards their input
+            let facing = dirs[i].unwrap().opposite().name();
+            let repeater = world
+                .add_block_state(&BlockState::new("minecraft:repeater").with("facing", facing));
+            world.set_block(pos.offset(0, 1, 0), repeater);
         } else {
-            world.set_block(cur.offset(0, 1, 0), repeater);
-            ss = 15;
+            world.set_block(pos.offset(0, 1, 0), wire);
         }
+        positions.extend([pos, pos.offset(0, 1, 0)]);
+    }
+    Ok(Routed {
+        output: *positions.last().unwrap(),
+        delay: repeaters.iter().filter(|&&repeater| repeater).count() as u32,
+        inverted: false,
+        ss: output,
+        footprint: footprint(&positions),
+    })
+}
 
-        if cur == end {
-            break;
+/// Every block along the path through `waypoints`, in order. The waypoints have to be at the
+/// same height.
+fn path_cells(waypoints: &[BlockPos]) -> Vec<BlockPos> {
+    let mut cells = waypoints.first().copied().into_iter().collect::<Vec<_>>();
+    for (i, &end) in waypoints.iter().enumerate().skip(1) {
+        let start = waypoints[i - 1];
+        let corner = BlockPos::new(end.x, start.y, start.z);
+        for target in [corner, end] {
+            let mut cur = *cells.last().unwrap();
+            while let Some(dir) = cur.direction_to(target) {
+                cur = cur.offset_dir(dir, 1);
+                cells.push(cur);
+            }
         }
-        cur = cur.offset_dir(dir, 1);
-        ss -= 1;
     }
+    cells
+}
+
+/// What one of the routing primitives below built.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Routed {
+    /// Where the signal comes out, which is the last wire, torch or observer
+    pub output: BlockPos,
+    /// Delay in redstone ticks
+    pub delay: u32,
+    /// Whether the output is off when the input is on
+    pub inverted: bool,
+    /// Strength of the signal at the output when it's on. Primitives that don't take an input
+    /// strength assume a full strength input.
+    pub ss: u32,
+    /// Every block that was placed
+    pub footprint: Region,
+}
+
+/// Draws a wire that moves one block in `dir` for every block it goes up, or down if `height`
+/// is negative. `start` is the block under the first wire. The signal loses one strength per
+/// step, so the staircase can climb at most 14 blocks.
+pub fn staircase(
+    world: &mut World,
+    block: u16,
+    start: BlockPos,
+    dir: BlockDirection,
+    height: i32,
+) -> Routed {
+    assert!(dir.is_horizontal(), "staircases must go horizontally");
+    assert!(
+        height.abs() < 15,
+        "the signal doesn't last for {} steps",
+        height.abs()
+    );
+    let wire = world.add_block("minecraft:redstone_wire");
+    let mut positions = Vec::new();
+    for step in 0..=height.abs() {
+        let pos = start
+            .offset_dir(dir, step)
+            .offset(0, step * height.signum(), 0);
+        world.set_block(pos, block);
+        world.set_block(pos.offset(0, 1, 0), wire);
+        positions.extend([pos, pos.offset(0, 1, 0)]);
+    }
+    Routed {
+        output: *positions.last().unwrap(),
+        delay: 0,
+        inverted: false,
+        ss: 15 - height.unsigned_abs(),
+        footprint: footprint(&positions),
+    }
+}
+
+/// Draws a wire straight up by `height` blocks, zigzagging between `start` and the block next
+/// to it in `dir`. `block` has to let the signal pass diagonally, so it should be glass or a
+/// top slab. Like staircases, towers can climb at most 14 blocks.
+pub fn tower(
+    world: &mut World,
+    block: u16,
+    start: BlockPos,
+    dir: BlockDirection,
+    height: i32,
+) -> Routed {
+    assert!(dir.is_horizontal(), "towers must step horizontally");
+    assert!(
+        (0..15).contains(&height),
+        "the signal doesn't last for {} steps",
+        height
+    );
+    let wire = world.add_block("minecraft:redstone_wire");
+    let mut positions = Vec::new();
+    for step in 0..=height {
+        let pos = start.offset_dir(dir, step % 2).offset(0, step, 0);
+        world.set_block(pos, block);
+        world.set_block(pos.offset(0, 1, 0), wire);
+        positions.extend([pos, pos.offset(0, 1, 0)]);
+    }
+    Routed {
+        output: *positions.last().unwrap(),
+        delay: 0,
+        inverted: false,
+        ss: 15 - height as u32,
+        footprint: footprint(&positions),
+    }
+}
+
+/// Builds a column of `block` with a torch on top of each, starting with a block at `start`
+/// that should be powered by the input. Every torch inverts the signal, so the tower uses the
+/// fewest torches that rise at least `height` blocks and come out inverted if `inverted` is
+/// set. The torches are placed in the state they'd have with the input off.
+pub fn torch_tower(
+    world: &mut World,
+    block: u16,
+    start: BlockPos,
+    height: i32,
+    inverted: bool,
+) -> Routed {
+    let mut torches = ((height.max(1) + 2) / 2) as u32;
+    if (torches % 2 == 1) != inverted {
+        torches += 1;
+    }
+    let lit = world.add_block("minecraft:redstone_torch[lit=true]");
+    let unlit = world.add_block("minecraft:redstone_torch[lit=false]");
+    let mut positions = Vec::new();
+    for i in 0..torches {
+        let pos = start.offset(0, i as i32 * 2, 0);
+        let torch = if i % 2 == 0 { lit } else { unlit };
+        world.set_block(pos, block);
+        world.set_block(pos.offset(0, 1, 0), torch);
+        positions.extend([pos, pos.offset(0, 1, 0)]);
+    }
+    Routed {
+        output: *positions.last().unwrap(),
+        delay: torches,
+        inverted,
+        ss: 15,
+        footprint: footprint(&positions),
+    }
+}
+
+/// Places `length` observers in a line from `start` in `dir`, each watching the one before
+/// it. Observers only pass on changes, so a change at the input comes out as a pulse.
+pub fn observer_chain(
+    world: &mut World,
+    start: BlockPos,
+    dir: BlockDirection,
+    length: i32,
+) -> Routed {
+    assert!(length > 0, "observer chains need at least one observer");
+    // Observers face the block they watch
+    let observer = world.add_block_state(
+        &BlockState::new("minecraft:observer").with("facing", dir.opposite().name()),
+    );
+    let positions = (0..length)
+        .map(|i| start.offset_dir(dir, i))
+        .collect::<Vec<_>>();
+    for &pos in &positions {
+        world.set_block(pos, observer);
+    }
+    Routed {
+        output: *positions.last().unwrap(),
+        delay: length as u32,
+        inverted: false,
+        ss: 15,
+        footprint: footprint(&positions),
+    }
+}
+
+/// The smallest region containing every one of `positions`.
+fn footprint(positions: &[BlockPos]) -> Region {
+    let start = Region::new(positions[0], positions[0]);
+    positions
+        .iter()
+        .fold(start, |region, &pos| region.including(pos))
 }
@@ -0,0 +1,145 @@
+//! Block entities, the extra data that blocks like containers, signs and comparators carry
+
+use crate::container::fill_items;
+use crate::world::map;
+use std::collections::HashMap;
+
+/// A block entity in java edition's layout. The position isn't part of it, since the world
+/// keeps track of that and every format stores it differently.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BlockEntity {
+    /// Namespaced id like `minecraft:chest`
+    pub id: String,
+    /// Every tag other than the id and position
+    pub data: HashMap<String, nbt::Value>,
+}
+
+/// An item stack in a container.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ItemStack {
+    pub id: String,
+    pub count: u8,
+}
+
+impl ItemStack {
+    /// Creates a stack of `count` items. The namespace can be left out of `id`, in which case it
+    /// defaults to `minecraft`.
+    pub fn new(id: &str, count: u8) -> Self {
+        let id = match id.contains(':') {
+            true => id.to_owned(),
+            false => format!("minecraft:{}", id),
+        };
+        Self { id, count }
+    }
+
+    fn to_nbt(&self, slot: usize) -> nbt::Value {
+        nbt::Value::Compound(map! {
+            "Count" => nbt::Value::Byte(self.count as i8),
+            "id" => nbt::Value::String(self.id.clone()),
+            "Slot" => nbt::Value::Byte(slot as i8)
+        })
+    }
+}
+
+impl BlockEntity {
+    /// Creates a block entity without any data. The namespace can be left out of `id`.
+    pub fn new(id: &str) -> Self {
+        let id = match id.contains(':') {
+            true => id.to_owned(),
+            false => format!("minecraft:{}", id),
+        };
+        Self {
+            id,
+            data: HashMap::new(),
+        }
+    }
+
+    /// Sets a tag, replacing any previous value.
+    pub fn with(mut self, key: &str, value: nbt::Value) -> Self {
+        self.data.insert(key.to_owned(), value);
+        self
+    }
+
+    /// A container, like a chest, hopper or dropper, holding `items` in its first slots.
+    pub fn container(id: &str, items: &[ItemStack]) -> Self {
+        let items = items
+            .iter()
+            .enumerate()
+            .map(|(slot, item)| item.to_nbt(slot))
+            .collect();
+        BlockEntity::new(id).with("Items", nbt::Value::List(items))
+    }
+
+    /// A barrel filled with as little redstone dust as possible so that a comparator reading it
+    /// outputs `ss`. Panics if `ss` is more than 15.
+    pub fn barrel(ss: u32) -> Self {
+        let items = fill_items(27, ss, "minecraft:redstone", 64)
+            .unwrap_or_else(|| panic!("barrels can't output a signal of {}", ss));
+        BlockEntity::container("minecraft:barrel", &items)
+    }
+
+    /// A sign with up to four lines of plain text. Every kind of sign and wall sign uses this
+    /// same block entity.
+    pub fn sign(lines: &[&str]) -> Self {
+        let mut sign = BlockEntity::new("minecraft:sign")
+            .with("Color", nbt::Value::String("black".to_owned()))
+            .with("GlowingText", nbt::Value::Byte(0));
+        for i in 0..4 {
+            let line = lines.get(i).copied().unwrap_or("");
+            let text = format!("{{\"text\":{}}}", json_string(line));
+            sign = sign.with(&format!("Text{}", i + 1), nbt::Value::String(text));
+        }
+        sign
+    }
+
+    /// A comparator outputting `ss`, which the game otherwise only updates once it's ticked.
+    pub fn comparator(ss: u32) -> Self {
+        BlockEntity::new("minecraft:comparator").with("OutputSignal", nbt::Value::Int(ss as i32))
+    }
+
+    /// A command block that needs redstone to run `command`.
+    pub fn command_block(command: &str) -> Self {
+        BlockEntity::new("minecraft:command_block")
+            .with("Command", nbt::Value::String(command.to_owned()))
+            .with("auto", nbt::Value::Byte(0))
+            .with("TrackOutput", nbt::Value::Byte(1))
+    }
+
+    /// A lectern holding a book and quill with `pages` of plain text, open on the first page.
+    pub fn lectern(pages: &[&str]) -> Self {
+        let pages = pages
+            .iter()
+            .map(|page| nbt::Value::String(page.to_string()))
+            .collect();
+        let book = map! {
+            "Count" => nbt::Value::Byte(1),
+            "id" => nbt::Value::String("minecraft:writable_book".to_owned()),
+            "tag" => nbt::Value::Compound(map! { "pages" => nbt::Value::List(pages) })
+        };
+        BlockEntity::new("minecraft:lectern")
+            .with("Book", nbt::Value::Compound(book))
+            .with("Page", nbt::Value::Int(0))
+    }
+
+    /// The id and data together, as most formats store them.
+    pub(crate) fn to_compound(&self) -> HashMap<String, nbt::Value> {
+        let mut compound = self.data.clone();
+        compound.insert("id".to_owned(), nbt::Value::String(self.id.clone()));
+        compound
+    }
+}
+
+/// Quotes `s` as a JSON string, which is how sign text is stored.
+fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
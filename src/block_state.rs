@@ -0,0 +1,105 @@
+//! Block states, like `minecraft:repeater[delay=2,facing=south]`
+
+use crate::error::{Error, Result};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// A block id along with its properties. Properties are kept sorted by name, so states that
+/// only differ in the order their properties were given in are equal and display the same.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BlockState {
+    pub namespace: String,
+    pub id: String,
+    pub properties: BTreeMap<String, String>,
+}
+
+impl BlockState {
+    /// Creates a state without any properties. The namespace can be left out of `name`, in
+    /// which case it defaults to `minecraft`.
+    pub fn new(name: &str) -> Self {
+        let (namespace, id) = name.split_once(':').unwrap_or(("minecraft", name));
+        Self {
+            namespace: namespace.to_owned(),
+            id: id.to_owned(),
+            properties: BTreeMap::new(),
+        }
+    }
+
+    /// Sets a property, replacing any previous value.
+    pub fn with(mut self, key: &str, value: impl ToString) -> Self {
+        self.set_property(key, value);
+        self
+    }
+
+    pub fn set_property(&mut self, key: &str, value: impl ToString) {
+        self.properties.insert(key.to_owned(), value.to_string());
+    }
+
+    pub fn property(&self, key: &str) -> Option<&str> {
+        self.properties.get(key).map(String::as_str)
+    }
+
+    /// Whether this state is matched by `mask`, which is a state with the same id whose
+    /// properties are a subset of this one's. `minecraft:repeater[facing=north]` matches
+    /// north facing repeaters of any delay, and `minecraft:repeater` matches every repeater.
+    pub fn matches(&self, mask: &BlockState) -> bool {
+        self.namespace == mask.namespace
+            && self.id == mask.id
+            && mask
+                .properties
+                .iter()
+                .all(|(key, value)| self.property(key) == Some(value.as_str()))
+    }
+
+    /// The namespaced id without properties, like `minecraft:repeater`.
+    pub fn name(&self) -> String {
+        format!("{}:{}", self.namespace, self.id)
+    }
+}
+
+impl fmt::Display for BlockState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.namespace, self.id)?;
+        if !self.properties.is_empty() {
+            let properties = self
+                .properties
+                .iter()
+                .map(|(k, v)| format!("{}={}", k, v))
+                .collect::<Vec<_>>();
+            write!(f, "[{}]", properties.join(","))?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for BlockState {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = |msg: &str| Error::InvalidBlockState(format!("{} in `{}`", msg, s));
+        let (name, properties) = match s.split_once('[') {
+            Some((name, rest)) => match rest.strip_suffix(']') {
+                Some(properties) => (name, Some(properties)),
+                None => return Err(invalid("unclosed property list")),
+            },
+            None => (s, None),
+        };
+        if name.is_empty() || name.ends_with(':') {
+            return Err(invalid("missing block id"));
+        }
+
+        let mut state = BlockState::new(name);
+        for property in properties.into_iter().flat_map(|p| p.split(',')) {
+            // Allows `[]` and trailing commas
+            if property.is_empty() {
+                continue;
+            }
+            match property.split_once('=') {
+                Some((key, value)) if !key.is_empty() => state.set_property(key, value),
+                _ => return Err(invalid("malformed property")),
+            }
+        }
+        Ok(state)
+    }
+}
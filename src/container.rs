@@ -0,0 +1,228 @@
+//! Filling comparator-readable blocks so that a comparator reading them outputs a given signal
+
+use crate::block_entity::{BlockEntity, ItemStack};
+use crate::block_state::BlockState;
+use crate::world::map;
+
+/// Music discs in 1.17 ordered by the signal a jukebox playing them outputs, starting at 1.
+const MUSIC_DISCS: [&str; 13] = [
+    "13", "cat", "blocks", "chirp", "far", "mall", "mellohi", "stal", "strad", "ward", "11",
+    "wait", "pigstep",
+];
+
+/// Blocks that a comparator can read a signal from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Container {
+    Barrel,
+    Chest,
+    TrappedChest,
+    /// Both halves of a chest, which comparators read as one inventory.
+    DoubleChest,
+    DoubleTrappedChest,
+    ShulkerBox,
+    Hopper,
+    Dropper,
+    Dispenser,
+    Furnace,
+    BlastFurnace,
+    Smoker,
+    BrewingStand,
+    /// Outputs the signal of the disc it's playing.
+    Jukebox,
+    /// Outputs a signal based on how far the book is turned.
+    Lectern,
+    /// Outputs the water level, from 0 to 3.
+    Cauldron,
+    /// Outputs the compost level, from 0 to 8.
+    Composter,
+}
+
+/// A block state and block entity that together make a comparator output a signal.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Filled {
+    pub state: BlockState,
+    pub entity: Option<BlockEntity>,
+    /// The left half of a double chest. Both halves face north, so it goes one block west of
+    /// `state`, which is the right half.
+    pub other_half: Option<(BlockState, BlockEntity)>,
+}
+
+impl Container {
+    /// The namespaced block id.
+    pub fn id(self) -> &'static str {
+        match self {
+            Container::Barrel => "minecraft:barrel",
+            Container::Chest | Container::DoubleChest => "minecraft:chest",
+            Container::TrappedChest | Container::DoubleTrappedChest => "minecraft:trapped_chest",
+            Container::ShulkerBox => "minecraft:shulker_box",
+            Container::Hopper => "minecraft:hopper",
+            Container::Dropper => "minecraft:dropper",
+            Container::Dispenser => "minecraft:dispenser",
+            Container::Furnace => "minecraft:furnace",
+            Container::BlastFurnace => "minecraft:blast_furnace",
+            Container::Smoker => "minecraft:smoker",
+            Container::BrewingStand => "minecraft:brewing_stand",
+            Container::Jukebox => "minecraft:jukebox",
+            Container::Lectern => "minecraft:lectern",
+            Container::Cauldron => "minecraft:cauldron",
+            Container::Composter => "minecraft:composter",
+        }
+    }
+
+    /// How many slots the container's inventory has, or `None` if the signal doesn't come
+    /// from an inventory.
+    pub fn slots(self) -> Option<usize> {
+        match self {
+            Container::Barrel
+            | Container::Chest
+            | Container::TrappedChest
+            | Container::ShulkerBox => Some(27),
+            Container::DoubleChest | Container::DoubleTrappedChest => Some(54),
+            Container::Hopper | Container::BrewingStand => Some(5),
+            Container::Dropper | Container::Dispenser => Some(9),
+            Container::Furnace | Container::BlastFurnace | Container::Smoker => Some(3),
+            Container::Jukebox
+            | Container::Lectern
+            | Container::Cauldron
+            | Container::Composter => None,
+        }
+    }
+
+    /// Fills the container with as few items as possible so that a comparator reading it outputs
+    /// `ss`. Inventories are filled with `item`, which stacks to `max_stack`, while the other
+    /// blocks ignore it. Returns `None` if `ss` can't be reached, like 2 in a hopper of
+    /// unstackable items, or if `max_stack` isn't between 1 and 64.
+    pub fn fill(self, ss: u32, item: &str, max_stack: u8) -> Option<Filled> {
+        let state = BlockState::new(self.id());
+        if matches!(self, Container::DoubleChest | Container::DoubleTrappedChest) {
+            let items = fill_items(54, ss, item, max_stack)?;
+            // The game counts the right half's slots first
+            let (right, left) = items.split_at(items.len().min(27));
+            let left = (
+                state.clone().with("type", "left"),
+                BlockEntity::container(self.id(), left),
+            );
+            return Some(Filled {
+                state: state.with("type", "right"),
+                entity: Some(BlockEntity::container(self.id(), right)),
+                other_half: Some(left),
+            });
+        }
+        if let Some(slots) = self.slots() {
+            let items = fill_items(slots, ss, item, max_stack)?;
+            let entity = BlockEntity::container(self.id(), &items);
+            return Some(Filled {
+                state,
+                entity: Some(entity),
+                other_half: None,
+            });
+        }
+
+        let filled = match self {
+            Container::Jukebox if ss == 0 => Filled {
+                state: state.with("has_record", false),
+                entity: None,
+                other_half: None,
+            },
+            Container::Jukebox => {
+                let disc = MUSIC_DISCS.get(ss as usize - 1)?;
+                let record = map! {
+                    "Count" => nbt::Value::Byte(1),
+                    "id" => nbt::Value::String(format!("minecraft:music_disc_{}", disc))
+                };
+                let entity = BlockEntity::new("minecraft:jukebox")
+                    .with("RecordItem", nbt::Value::Compound(record));
+                Filled {
+                    state: state.with("has_record", true),
+                    entity: Some(entity),
+                    other_half: None,
+                }
+            }
+            Container::Lectern if ss == 0 => Filled {
+                state: state.with("has_book", false),
+                entity: None,
+                other_half: None,
+            },
+            Container::Lectern => {
+                let (pages, page) = (1..=15)
+                    .flat_map(|pages| (0..pages).map(move |page| (pages, page)))
+                    .find(|&(pages, page)| lectern_output(pages, page) == ss)?;
+                let mut entity = BlockEntity::lectern(&vec![""; pages as usize]);
+                entity
+                    .data
+                    .insert("Page".to_owned(), nbt::Value::Int(page as i32));
+                Filled {
+                    state: state.with("has_book", true),
+                    entity: Some(entity),
+                    other_half: None,
+                }
+            }
+            // Empty cauldrons are a different block from ones with water in them
+            Container::Cauldron if ss == 0 => Filled {
+                state,
+                entity: None,
+                other_half: None,
+            },
+            Container::Cauldron if ss <= 3 => Filled {
+                state: BlockState::new("minecraft:water_cauldron").with("level", ss),
+                entity: None,
+                other_half: None,
+            },
+            Container::Composter if ss <= 8 => Filled {
+                state: state.with("level", ss),
+                entity: None,
+                other_half: None,
+            },
+            _ => return None,
+        };
+        Some(filled)
+    }
+}
+
+/// The fewest items of a kind that stacks to `max_stack` that make a comparator read `ss` from
+/// an inventory with `slots` slots. Full stacks come first, followed by whatever is left over.
+/// Returns `None` if `ss` can't be reached or `max_stack` isn't between 1 and 64, which is as
+/// far as items stack.
+pub fn fill_items(slots: usize, ss: u32, item: &str, max_stack: u8) -> Option<Vec<ItemStack>> {
+    if !(1..=64).contains(&max_stack) {
+        return None;
+    }
+    let max_stack = max_stack as usize;
+    let stacks = |count: usize| {
+        (0..count)
+            .step_by(max_stack)
+            .map(|added| ItemStack::new(item, (count - added).min(max_stack) as u8))
+            .collect::<Vec<_>>()
+    };
+    (0..=slots * max_stack).map(stacks).find(|items| {
+        let counts = items
+            .iter()
+            .map(|item| (item.count, max_stack as u8))
+            .collect::<Vec<_>>();
+        signal_strength(slots, &counts) == ss
+    })
+}
+
+/// The signal a comparator reads from an inventory with `slots` slots holding stacks of
+/// `(count, max_stack)`. This follows the game's calculation, floating point and all.
+pub fn signal_strength(slots: usize, stacks: &[(u8, u8)]) -> u32 {
+    let mut fullness = 0.0f32;
+    let mut non_empty = 0;
+    for &(count, max_stack) in stacks {
+        if count > 0 {
+            fullness += count as f32 / max_stack.min(64) as f32;
+            non_empty += 1;
+        }
+    }
+    fullness /= slots as f32;
+    (fullness * 14.0).floor() as u32 + (non_empty > 0) as u32
+}
+
+/// The signal a comparator reads from a lectern holding a book of `pages` opened at `page`.
+pub fn lectern_output(pages: u32, page: u32) -> u32 {
+    let progress = match pages {
+        0 | 1 => 1.0f32,
+        _ => page as f32 / (pages as f32 - 1.0),
+    };
+    (progress * 14.0).floor() as u32 + 1
+}
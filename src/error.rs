@@ -0,0 +1,126 @@
+use crate::world::BlockPos;
+use std::fmt;
+use std::io;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Nbt(nbt::Error),
+    /// A tag required by the format is absent or has the wrong type.
+    MissingTag(&'static str),
+    UnsupportedVersion(i32),
+    /// A block state couldn't be parsed from the bracket syntax.
+    InvalidBlockState(String),
+    InvalidSchematic(String),
+    /// A chunk in an existing region file is corrupt or in a format that can't be updated.
+    InvalidChunk(String),
+    /// Part of the world would be outside of the heights the game keeps blocks at.
+    OutsideBuildHeight {
+        y: i32,
+    },
+    /// The world is too big to be stored in the chosen format.
+    DimensionsTooLarge {
+        size: (usize, usize, usize),
+        max: usize,
+    },
+    /// A function can't hold the requested number of commands along with the command that
+    /// schedules the next part.
+    TooFewCommands(usize),
+    /// A world's palette has run out of ids for new block states.
+    PaletteFull,
+    /// A signal strength is outside of the 0 to 15 that redstone can carry.
+    InvalidSignalStrength(u32),
+    /// A wire ends with a weaker signal than it was asked to.
+    SignalTooWeak {
+        ss: u32,
+        min: u32,
+    },
+    /// A wire runs out of signal at a place where a repeater can't go.
+    SignalLost {
+        at: BlockPos,
+    },
+    /// The router couldn't find a path that fits between two points.
+    NoRoute {
+        from: BlockPos,
+        to: BlockPos,
+    },
+    /// A bit of a bus has no room for the repeaters it needs to match the slowest bit.
+    DelayMismatch {
+        from: BlockPos,
+        to: BlockPos,
+        missing: u32,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "io error: {}", err),
+            Error::Nbt(err) => write!(f, "nbt error: {}", err),
+            Error::MissingTag(tag) => write!(f, "missing or mistyped tag `{}`", tag),
+            Error::UnsupportedVersion(version) => {
+                write!(f, "unsupported schematic version {}", version)
+            }
+            Error::InvalidBlockState(msg) => write!(f, "invalid block state: {}", msg),
+            Error::InvalidSchematic(msg) => write!(f, "invalid schematic: {}", msg),
+            Error::InvalidChunk(msg) => write!(f, "invalid chunk: {}", msg),
+            Error::OutsideBuildHeight { y } => {
+                write!(f, "y = {} is outside of the build height of 0 to 255", y)
+            }
+            Error::DimensionsTooLarge { size, max } => write!(
+                f,
+                "world size {:?} exceeds the maximum of {} in each dimension",
+                size, max
+            ),
+            Error::TooFewCommands(max) => write!(
+                f,
+                "functions need room for at least 2 commands but only {} were allowed",
+                max
+            ),
+            Error::PaletteFull => write!(f, "the palette can't hold more than 65536 block states"),
+            Error::InvalidSignalStrength(ss) => write!(f, "invalid signal strength {}", ss),
+            Error::SignalTooWeak { ss, min } => write!(
+                f,
+                "the wire ends with a signal of {} where at least {} is needed",
+                ss, min
+            ),
+            Error::SignalLost { at } => {
+                write!(
+                    f,
+                    "the signal runs out at {:?} with no room for a repeater",
+                    at
+                )
+            }
+            Error::NoRoute { from, to } => write!(f, "no route from {:?} to {:?}", from, to),
+            Error::DelayMismatch { from, to, missing } => write!(
+                f,
+                "the bit from {:?} to {:?} is {} ticks faster than the bus with no room to slow down",
+                from, to, missing
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            Error::Nbt(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+impl From<nbt::Error> for Error {
+    fn from(err: nbt::Error) -> Self {
+        Error::Nbt(err)
+    }
+}
@@ -1,2 +1,15 @@
+pub mod anvil;
 pub mod basic;
+pub mod block_entity;
+pub mod block_state;
+pub mod container;
+pub mod error;
+pub mod litematic;
+pub mod mcfunction;
+pub mod mcstructure;
+pub mod region;
+pub mod router;
+pub mod structure;
+pub mod transform;
+pub mod varint;
 pub mod world;
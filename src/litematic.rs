@@ -0,0 +1,180 @@
+//! Export to Litematica's `.litematic` format
+
+use crate::error::Result;
+use crate::world::{PaletteEntry, World, MC_DATA_VERSION};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The litematic format version used by Litematica for 1.17
+const LITEMATIC_VERSION: i32 = 5;
+
+impl World {
+    pub fn save_litematic(&self, file_name: &str, name: &str, author: &str) -> Result<()> {
+        let data = self.litematic_data(name, author)?;
+        let mut file = File::create(file_name)?;
+        file.write_all(&data)?;
+        Ok(())
+    }
+
+    /// Encodes the world as a litematic with a single region called `name`.
+    pub fn litematic_data(&self, name: &str, author: &str) -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+        self.check_size(i32::MAX as usize)?;
+        let (sx, sy, sz) = self.size();
+        let min = self.min();
+
+        let block_state_palette = self.palette_entries();
+
+        let bits = (usize::BITS - (block_state_palette.len() - 1).leading_zeros()).max(2);
+        let mut block_states = PackedArray::new(bits, sx * sy * sz);
+        let mut total_blocks = 0;
+        let mut idx = 0;
+        self.for_each_block(min, (sx, sy, sz), |_, block| {
+            if block != 0 {
+                total_blocks += 1;
+            }
+            block_states.set(idx, block as u64);
+            idx += 1;
+        });
+
+        let mut tile_entities = Vec::new();
+        for (pos, entity) in self.block_entities() {
+            let pos = pos - min;
+            let mut blob = nbt::Blob::new();
+            for (key, value) in entity.to_compound() {
+                blob.insert(key, value)?;
+            }
+            blob.insert("x", pos.x)?;
+            blob.insert("y", pos.y)?;
+            blob.insert("z", pos.z)?;
+            tile_entities.push(blob);
+        }
+
+        let size = Vec3 {
+            x: sx as i32,
+            y: sy as i32,
+            z: sz as i32,
+        };
+        let region = Region {
+            position: Vec3 { x: 0, y: 0, z: 0 },
+            size,
+            block_state_palette,
+            block_states: block_states.data,
+            tile_entities,
+            entities: Vec::new(),
+            pending_block_ticks: Vec::new(),
+            pending_fluid_ticks: Vec::new(),
+        };
+
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_millis() as i64)
+            .unwrap_or(0);
+        let metadata = Metadata {
+            name: name.to_owned(),
+            author: author.to_owned(),
+            description: String::new(),
+            region_count: 1,
+            total_blocks,
+            total_volume: (sx * sy * sz) as i32,
+            enclosing_size: size,
+            time_created: time,
+            time_modified: time,
+        };
+
+        let mut regions = HashMap::new();
+        regions.insert(name.to_owned(), region);
+        let litematic = Litematic {
+            minecraft_data_version: MC_DATA_VERSION,
+            version: LITEMATIC_VERSION,
+            metadata,
+            regions,
+        };
+        nbt::to_gzip_writer(&mut out, &litematic, None)?;
+
+        Ok(out)
+    }
+}
+
+/// Litematica's bit array, where entries are tightly packed and may span two longs.
+struct PackedArray {
+    bits: u32,
+    data: Vec<i64>,
+}
+
+impl PackedArray {
+    fn new(bits: u32, len: usize) -> Self {
+        let longs = (len * bits as usize).div_ceil(64);
+        Self {
+            bits,
+            data: vec![0; longs],
+        }
+    }
+
+    fn set(&mut self, idx: usize, value: u64) {
+        let bits = self.bits as usize;
+        let mask = (1u64 << bits) - 1;
+        let value = value & mask;
+
+        let start_offset = idx * bits;
+        let start_long = start_offset / 64;
+        let end_long = ((idx + 1) * bits - 1) / 64;
+        let start_bit = start_offset % 64;
+
+        let start = self.data[start_long] as u64;
+        self.data[start_long] = (start & !(mask << start_bit) | value << start_bit) as i64;
+        if start_long != end_long {
+            let end_offset = 64 - start_bit;
+            let j = bits - end_offset;
+            let end = self.data[end_long] as u64;
+            self.data[end_long] = (end >> j << j | value >> end_offset) as i64;
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Copy)]
+struct Vec3 {
+    x: i32,
+    y: i32,
+    z: i32,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct Region {
+    position: Vec3,
+    size: Vec3,
+    block_state_palette: Vec<PaletteEntry>,
+    #[serde(serialize_with = "nbt::i64_array")]
+    block_states: Vec<i64>,
+    tile_entities: Vec<nbt::Blob>,
+    entities: Vec<nbt::Blob>,
+    pending_block_ticks: Vec<nbt::Blob>,
+    pending_fluid_ticks: Vec<nbt::Blob>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct Metadata {
+    name: String,
+    author: String,
+    description: String,
+    region_count: i32,
+    total_blocks: i32,
+    total_volume: i32,
+    enclosing_size: Vec3,
+    time_created: i64,
+    time_modified: i64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct Litematic {
+    minecraft_data_version: i32,
+    version: i32,
+    metadata: Metadata,
+    regions: HashMap<String, Region>,
+}
@@ -0,0 +1,199 @@
+//! Export to datapack functions made of `/fill` and `/setblock` commands
+
+use crate::error::{Error, Result};
+use crate::world::{BlockPos, World};
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+
+/// The most blocks a single `/fill` is allowed to change
+const MAX_FILL_VOLUME: usize = 32768;
+
+impl World {
+    /// Writes the world as a chain of functions into `dir`, which should be the `functions`
+    /// directory of the namespace in `function`. For example, with a `function` of `rvc:lwsp`,
+    /// `lwsp_0.mcfunction`, `lwsp_1.mcfunction`, etc. are created and running `rvc:lwsp_0`
+    /// places the whole world with its origin at `origin`. Parts left over from an earlier,
+    /// longer export of the same function are deleted so they can't schedule each other.
+    pub fn save_mcfunction(
+        &self,
+        dir: &str,
+        function: &str,
+        origin: BlockPos,
+        max_commands: usize,
+    ) -> Result<()> {
+        let path = function.split_once(':').map_or(function, |(_, path)| path);
+        let parts = self.mcfunction_data(function, origin, max_commands)?;
+        let file_name = |i| Path::new(dir).join(format!("{}_{}.mcfunction", path, i));
+        for (i, part) in parts.iter().enumerate() {
+            let file_name = file_name(i);
+            if let Some(parent) = file_name.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let mut file = File::create(file_name)?;
+            file.write_all(part.as_bytes())?;
+        }
+        for i in parts.len().. {
+            let file_name = file_name(i);
+            if !file_name.exists() {
+                break;
+            }
+            fs::remove_file(file_name)?;
+        }
+        Ok(())
+    }
+
+    /// Generates the commands to place the world with its origin at `origin`. Air is skipped,
+    /// so existing blocks aren't cleared.
+    ///
+    /// The commands are split into parts of at most `max_commands` commands each, and every
+    /// part schedules the next one for the following tick so that no single tick runs into the
+    /// command chain limit. Scheduled functions run at world spawn rather than where the first
+    /// part was run, so the coordinates are absolute. Parts are named `function` followed by
+    /// `_0`, `_1`, etc. A `max_commands` below 2 leaves no room for the schedule command and
+    /// is an error.
+    pub fn mcfunction_data(
+        &self,
+        function: &str,
+        origin: BlockPos,
+        max_commands: usize,
+    ) -> Result<Vec<String>> {
+        if max_commands < 2 {
+            return Err(Error::TooFewCommands(max_commands));
+        }
+        let (sx, sy, sz) = self.size();
+        let min = self.min();
+        let palette = self.palette();
+        let coords = |pos: BlockPos| {
+            let pos = origin + pos;
+            format!("{} {} {}", pos.x, pos.y, pos.z)
+        };
+        let world_pos = |x: usize, y: usize, z: usize| min.offset(x as i32, y as i32, z as i32);
+
+        let mut commands = Vec::new();
+        let mut placed = vec![false; sx * sy * sz];
+        let idx = |x: usize, y: usize, z: usize| (y * sz + z) * sx + x;
+        for y in 0..sy {
+            for z in 0..sz {
+                for x in 0..sx {
+                    let pos = world_pos(x, y, z);
+                    let block = self.get_block(pos);
+                    if block == 0 || placed[idx(x, y, z)] {
+                        continue;
+                    }
+
+                    if let Some(entity) = self.get_block_entity(pos) {
+                        placed[idx(x, y, z)] = true;
+                        let data = nbt::Value::Compound(entity.data.clone());
+                        commands.push(format!(
+                            "setblock {} {}{}",
+                            coords(pos),
+                            palette[block as usize],
+                            snbt(&data)
+                        ));
+                        continue;
+                    }
+
+                    // Greedily grow a cuboid of the same block along x, then z, then y
+                    let fits = |x: usize, y: usize, z: usize| {
+                        let pos = world_pos(x, y, z);
+                        !placed[idx(x, y, z)]
+                            && self.get_block(pos) == block
+                            && self.get_block_entity(pos).is_none()
+                    };
+                    let mut ex = x + 1;
+                    while ex < sx && ex - x < MAX_FILL_VOLUME && fits(ex, y, z) {
+                        ex += 1;
+                    }
+                    let mut ez = z + 1;
+                    while ez < sz
+                        && (ex - x) * (ez + 1 - z) <= MAX_FILL_VOLUME
+                        && (x..ex).all(|x| fits(x, y, ez))
+                    {
+                        ez += 1;
+                    }
+                    let mut ey = y + 1;
+                    while ey < sy
+                        && (ex - x) * (ez - z) * (ey + 1 - y) <= MAX_FILL_VOLUME
+                        && (z..ez).all(|z| (x..ex).all(|x| fits(x, ey, z)))
+                    {
+                        ey += 1;
+                    }
+
+                    for y in y..ey {
+                        for z in z..ez {
+                            for x in x..ex {
+                                placed[idx(x, y, z)] = true;
+                            }
+                        }
+                    }
+
+                    let end = world_pos(ex - 1, ey - 1, ez - 1);
+                    if end == pos {
+                        commands.push(format!(
+                            "setblock {} {}",
+                            coords(pos),
+                            palette[block as usize]
+                        ));
+                    } else {
+                        commands.push(format!(
+                            "fill {} {} {}",
+                            coords(pos),
+                            coords(end),
+                            palette[block as usize]
+                        ));
+                    }
+                }
+            }
+        }
+
+        let chunks = commands.chunks(max_commands - 1).collect::<Vec<_>>();
+        let mut parts = Vec::new();
+        for (i, chunk) in chunks.iter().enumerate() {
+            let mut part = chunk.join("\n");
+            if i + 1 < chunks.len() {
+                part.push_str(&format!("\nschedule function {}_{} 1t", function, i + 1));
+            }
+            part.push('\n');
+            parts.push(part);
+        }
+        Ok(parts)
+    }
+}
+
+/// Formats an NBT value as SNBT, like it would be written in a command.
+fn snbt(value: &nbt::Value) -> String {
+    match value {
+        nbt::Value::Byte(v) => format!("{}b", v),
+        nbt::Value::Short(v) => format!("{}s", v),
+        nbt::Value::Int(v) => v.to_string(),
+        nbt::Value::Long(v) => format!("{}L", v),
+        nbt::Value::Float(v) => format!("{}f", v),
+        nbt::Value::Double(v) => format!("{}d", v),
+        nbt::Value::String(v) => format!("\"{}\"", v.replace('\\', "\\\\").replace('"', "\\\"")),
+        nbt::Value::List(values) => {
+            let values = values.iter().map(snbt).collect::<Vec<_>>();
+            format!("[{}]", values.join(","))
+        }
+        nbt::Value::Compound(values) => {
+            let mut entries = values
+                .iter()
+                .map(|(k, v)| format!("{}:{}", k, snbt(v)))
+                .collect::<Vec<_>>();
+            entries.sort();
+            format!("{{{}}}", entries.join(","))
+        }
+        nbt::Value::ByteArray(values) => {
+            let values = values.iter().map(|v| format!("{}b", v)).collect::<Vec<_>>();
+            format!("[B;{}]", values.join(","))
+        }
+        nbt::Value::IntArray(values) => {
+            let values = values.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+            format!("[I;{}]", values.join(","))
+        }
+        nbt::Value::LongArray(values) => {
+            let values = values.iter().map(|v| format!("{}L", v)).collect::<Vec<_>>();
+            format!("[L;{}]", values.join(","))
+        }
+    }
+}
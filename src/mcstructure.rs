@@ -0,0 +1,313 @@
+//! Export to Bedrock Edition's `.mcstructure` format
+
+use crate::block_entity::BlockEntity;
+use crate::block_state::BlockState;
+use crate::error::Result;
+use crate::world::{map, BlockDirection, World};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Write;
+
+/// Block state version of Bedrock 1.19.0, which the translations below are written against
+const BEDROCK_BLOCK_VERSION: i32 = (1 << 24) | (19 << 16);
+
+impl World {
+    pub fn save_mcstructure(&self, file_name: &str) -> Result<()> {
+        let data = self.mcstructure_data()?;
+        let mut file = File::create(file_name)?;
+        file.write_all(&data)?;
+        Ok(())
+    }
+
+    pub fn mcstructure_data(&self) -> Result<Vec<u8>> {
+        self.check_size(i32::MAX as usize)?;
+        let (sx, sy, sz) = self.size();
+        let min = self.min();
+
+        // Different java states can become the same bedrock state, wire connections for example
+        let mut block_palette = Vec::new();
+        let mut bedrock_ids = HashMap::new();
+        let mut remap = Vec::new();
+        for state in self.palette() {
+            let (name, states) = bedrock_block(state);
+            let mut key = states
+                .iter()
+                .map(|(k, v)| format!("{}={:?}", k, v))
+                .collect::<Vec<_>>();
+            key.sort();
+            let key = format!("{}[{}]", name, key.join(","));
+            let id = *bedrock_ids.entry(key).or_insert_with(|| {
+                block_palette.push(nbt::Value::Compound(map! {
+                    "name" => nbt::Value::String(name),
+                    "states" => nbt::Value::Compound(states),
+                    "version" => nbt::Value::Int(BEDROCK_BLOCK_VERSION)
+                }));
+                block_palette.len() as i32 - 1
+            });
+            remap.push(id);
+        }
+
+        // Blocks are ordered with z increasing fastest, then y, then x
+        let mut blocks = vec![nbt::Value::Int(0); sx * sy * sz];
+        self.for_each_block(min, (sx, sy, sz), |pos, block| {
+            let pos = pos - min;
+            let idx = (pos.x as usize * sy + pos.y as usize) * sz + pos.z as usize;
+            blocks[idx] = nbt::Value::Int(remap[block as usize]);
+        });
+        // The second layer holds waterlogging, which we never use
+        let liquids = vec![nbt::Value::Int(-1); blocks.len()];
+
+        let mut block_position_data = HashMap::new();
+        for (pos, entity) in self.block_entities() {
+            let pos = pos - min;
+            let mut block_entity = bedrock_block_entity(entity);
+            block_entity.insert("isMovable".to_owned(), nbt::Value::Byte(1));
+            block_entity.insert("x".to_owned(), nbt::Value::Int(pos.x));
+            block_entity.insert("y".to_owned(), nbt::Value::Int(pos.y));
+            block_entity.insert("z".to_owned(), nbt::Value::Int(pos.z));
+            let idx = (pos.x as usize * sy + pos.y as usize) * sz + pos.z as usize;
+            block_position_data.insert(
+                idx.to_string(),
+                nbt::Value::Compound(map! {
+                    "block_entity_data" => nbt::Value::Compound(block_entity)
+                }),
+            );
+        }
+
+        let palette = map! {
+            "default" => nbt::Value::Compound(map! {
+                "block_palette" => nbt::Value::List(block_palette),
+                "block_position_data" => nbt::Value::Compound(block_position_data)
+            })
+        };
+        let structure = map! {
+            "block_indices" => nbt::Value::List(vec![
+                nbt::Value::List(blocks),
+                nbt::Value::List(liquids)
+            ]),
+            "entities" => nbt::Value::List(Vec::new()),
+            "palette" => nbt::Value::Compound(palette)
+        };
+        let size = vec![sx as i32, sy as i32, sz as i32];
+        let root = map! {
+            "format_version" => nbt::Value::Int(1),
+            "size" => nbt::Value::List(size.into_iter().map(nbt::Value::Int).collect()),
+            "structure" => nbt::Value::Compound(structure),
+            "structure_world_origin" => nbt::Value::List(vec![nbt::Value::Int(0); 3])
+        };
+
+        let mut out = Vec::new();
+        out.push(0x0a);
+        write_string(&mut out, "");
+        write_payload(&mut out, &nbt::Value::Compound(root));
+        Ok(out)
+    }
+}
+
+/// Translates a java block into its bedrock name and block states. Blocks without a
+/// translation keep their name and lose their properties.
+fn bedrock_block(block: &BlockState) -> (String, HashMap<String, nbt::Value>) {
+    let prop = |name| block.property(name);
+    let id = block.id.as_str();
+
+    let mut states = HashMap::new();
+    let name = match id {
+        "redstone_wire" => {
+            let power = prop("power").and_then(|p| p.parse().ok()).unwrap_or(0);
+            states.insert("redstone_signal".to_owned(), nbt::Value::Int(power));
+            "redstone_wire"
+        }
+        "repeater" => {
+            // Bedrock stores the direction the signal travels, java stores the opposite
+            let direction = match prop("facing").unwrap_or("north") {
+                "north" => 0,
+                "east" => 1,
+                "south" => 2,
+                _ => 3,
+            };
+            let delay = prop("delay").and_then(|d| d.parse().ok()).unwrap_or(1);
+            states.insert("direction".to_owned(), nbt::Value::Int(direction));
+            states.insert("repeater_delay".to_owned(), nbt::Value::Int(delay - 1));
+            match prop("powered") {
+                Some("true") => "powered_repeater",
+                _ => "unpowered_repeater",
+            }
+        }
+        "redstone_torch" | "redstone_wall_torch" => {
+            // Bedrock stores the side the torch is attached to, java the way it points
+            let facing = match id {
+                "redstone_torch" => "top",
+                _ => prop("facing")
+                    .and_then(BlockDirection::from_name)
+                    .unwrap_or(BlockDirection::North)
+                    .opposite()
+                    .name(),
+            };
+            states.insert(
+                "torch_facing_direction".to_owned(),
+                nbt::Value::String(facing.to_owned()),
+            );
+            match prop("lit") {
+                Some("false") => "unlit_redstone_torch",
+                _ => "redstone_torch",
+            }
+        }
+        "smooth_stone_slab" => {
+            let slab_type = prop("type").unwrap_or("bottom");
+            states.insert(
+                "stone_slab_type".to_owned(),
+                nbt::Value::String("smooth_stone".to_owned()),
+            );
+            states.insert(
+                "top_slot_bit".to_owned(),
+                nbt::Value::Byte((slab_type == "top") as i8),
+            );
+            match slab_type {
+                "double" => "double_stone_block_slab",
+                _ => "stone_block_slab",
+            }
+        }
+        "barrel" => {
+            let facing = match prop("facing").unwrap_or("north") {
+                "down" => 0,
+                "up" => 1,
+                "north" => 2,
+                "south" => 3,
+                "west" => 4,
+                _ => 5,
+            };
+            let open = (prop("open") == Some("true")) as i8;
+            states.insert("facing_direction".to_owned(), nbt::Value::Int(facing));
+            states.insert("open_bit".to_owned(), nbt::Value::Byte(open));
+            "barrel"
+        }
+        _ if id.ends_with("_concrete") => {
+            let color = match id.trim_end_matches("_concrete") {
+                "light_gray" => "silver",
+                color => color,
+            };
+            states.insert("color".to_owned(), nbt::Value::String(color.to_owned()));
+            "concrete"
+        }
+        _ => id,
+    };
+    (format!("{}:{}", block.namespace, name), states)
+}
+
+/// Translates a java block entity into bedrock's, without its position. Tags that both
+/// editions share, like `Items`, `OutputSignal` and `Command`, are kept as they are.
+fn bedrock_block_entity(entity: &BlockEntity) -> HashMap<String, nbt::Value> {
+    let mut data = entity.data.clone();
+    let id = entity.id.trim_start_matches("minecraft:");
+    let bedrock_id = match id {
+        "trapped_chest" => "Chest".to_owned(),
+        "command_block" | "chain_command_block" | "repeating_command_block" => {
+            "CommandBlock".to_owned()
+        }
+        _ if id.ends_with("sign") => {
+            // Bedrock keeps all four lines in a single plain text string
+            let lines = (1..=4)
+                .map(|i| match data.remove(&format!("Text{}", i)) {
+                    Some(nbt::Value::String(line)) => json_text(&line),
+                    _ => String::new(),
+                })
+                .collect::<Vec<_>>();
+            data.retain(|key, _| key == "Color" || key == "GlowingText");
+            data.insert("Text".to_owned(), nbt::Value::String(lines.join("\n")));
+            "Sign".to_owned()
+        }
+        _ if id.ends_with("shulker_box") => "ShulkerBox".to_owned(),
+        // Everything else is the java id in upper camel case, like `Barrel` or `Hopper`
+        _ => id
+            .split('_')
+            .map(|word| {
+                let mut chars = word.chars();
+                chars.next().map_or(String::new(), |first| {
+                    first.to_uppercase().chain(chars).collect()
+                })
+            })
+            .collect(),
+    };
+    if let Some(nbt::Value::List(items)) = data.remove("Items") {
+        let items = items.into_iter().map(bedrock_item).collect();
+        data.insert("Items".to_owned(), nbt::Value::List(items));
+    }
+    if let Some(book) = data.remove("Book") {
+        data.insert("book".to_owned(), bedrock_item(book));
+        data.insert("hasBook".to_owned(), nbt::Value::Byte(1));
+    }
+    data.insert("id".to_owned(), nbt::Value::String(bedrock_id));
+    data
+}
+
+/// Pulls the plain text out of a JSON text component like `{"text":"..."}`.
+fn json_text(json: &str) -> String {
+    let text = json
+        .strip_prefix("{\"text\":\"")
+        .and_then(|text| text.strip_suffix("\"}"))
+        .unwrap_or(json);
+    text.replace("\\\"", "\"").replace("\\\\", "\\")
+}
+
+/// Converts a java item stack into the bedrock representation.
+fn bedrock_item(item: nbt::Value) -> nbt::Value {
+    let mut item = match item {
+        nbt::Value::Compound(item) => item,
+        item => return item,
+    };
+    if let Some(id) = item.remove("id") {
+        item.insert("Name".to_owned(), id);
+    }
+    item.insert("Damage".to_owned(), nbt::Value::Short(0));
+    item.insert("WasPickedUp".to_owned(), nbt::Value::Byte(0));
+    nbt::Value::Compound(item)
+}
+
+fn write_string(out: &mut Vec<u8>, value: &str) {
+    out.extend_from_slice(&(value.len() as u16).to_le_bytes());
+    out.extend_from_slice(value.as_bytes());
+}
+
+/// Writes a value in bedrock's little endian variant of NBT, without its tag id and name.
+fn write_payload(out: &mut Vec<u8>, value: &nbt::Value) {
+    match value {
+        nbt::Value::Byte(v) => out.push(*v as u8),
+        nbt::Value::Short(v) => out.extend_from_slice(&v.to_le_bytes()),
+        nbt::Value::Int(v) => out.extend_from_slice(&v.to_le_bytes()),
+        nbt::Value::Long(v) => out.extend_from_slice(&v.to_le_bytes()),
+        nbt::Value::Float(v) => out.extend_from_slice(&v.to_le_bytes()),
+        nbt::Value::Double(v) => out.extend_from_slice(&v.to_le_bytes()),
+        nbt::Value::ByteArray(values) => {
+            out.extend_from_slice(&(values.len() as i32).to_le_bytes());
+            out.extend(values.iter().map(|&v| v as u8));
+        }
+        nbt::Value::String(v) => write_string(out, v),
+        nbt::Value::List(values) => {
+            out.push(values.first().map_or(0, |v| v.id()));
+            out.extend_from_slice(&(values.len() as i32).to_le_bytes());
+            for value in values {
+                write_payload(out, value);
+            }
+        }
+        nbt::Value::Compound(values) => {
+            for (name, value) in values {
+                out.push(value.id());
+                write_string(out, name);
+                write_payload(out, value);
+            }
+            out.push(0);
+        }
+        nbt::Value::IntArray(values) => {
+            out.extend_from_slice(&(values.len() as i32).to_le_bytes());
+            for v in values {
+                out.extend_from_slice(&v.to_le_bytes());
+            }
+        }
+        nbt::Value::LongArray(values) => {
+            out.extend_from_slice(&(values.len() as i32).to_le_bytes());
+            for v in values {
+                out.extend_from_slice(&v.to_le_bytes());
+            }
+        }
+    }
+}
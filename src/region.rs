@@ -0,0 +1,166 @@
+//! Editing cuboid regions of a world at once, along the lines of WorldEdit's commands
+
+use crate::block_state::BlockState;
+use crate::world::{BlockDirection, BlockPos, PasteOptions, World};
+
+/// A box of blocks between two corners, both of which are inside it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Region {
+    pub min: BlockPos,
+    pub max: BlockPos,
+}
+
+impl Region {
+    /// Creates the region spanning from `a` to `b`, which can be any two opposite corners.
+    pub fn new(a: BlockPos, b: BlockPos) -> Self {
+        Self {
+            min: BlockPos::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z)),
+            max: BlockPos::new(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z)),
+        }
+    }
+
+    pub fn size(&self) -> (usize, usize, usize) {
+        (
+            (self.max.x - self.min.x + 1) as usize,
+            (self.max.y - self.min.y + 1) as usize,
+            (self.max.z - self.min.z + 1) as usize,
+        )
+    }
+
+    pub fn contains(&self, pos: BlockPos) -> bool {
+        (self.min.x..=self.max.x).contains(&pos.x)
+            && (self.min.y..=self.max.y).contains(&pos.y)
+            && (self.min.z..=self.max.z).contains(&pos.z)
+    }
+
+    /// Grows the region just enough to contain `pos`.
+    pub fn including(self, pos: BlockPos) -> Region {
+        Region {
+            min: BlockPos::new(
+                self.min.x.min(pos.x),
+                self.min.y.min(pos.y),
+                self.min.z.min(pos.z),
+            ),
+            max: BlockPos::new(
+                self.max.x.max(pos.x),
+                self.max.y.max(pos.y),
+                self.max.z.max(pos.z),
+            ),
+        }
+    }
+
+    /// Moves the region by `amt` blocks in `dir`.
+    pub fn offset_dir(self, dir: BlockDirection, amt: i32) -> Region {
+        Region {
+            min: self.min.offset_dir(dir, amt),
+            max: self.max.offset_dir(dir, amt),
+        }
+    }
+
+    /// How many blocks the region spans in `dir`.
+    fn length(&self, dir: BlockDirection) -> i32 {
+        let (sx, sy, sz) = self.size();
+        match dir {
+            BlockDirection::East | BlockDirection::West => sx as i32,
+            BlockDirection::Up | BlockDirection::Down => sy as i32,
+            BlockDirection::North | BlockDirection::South => sz as i32,
+        }
+    }
+}
+
+impl World {
+    /// The smallest region containing every block that isn't air, or `None` if there are none.
+    pub fn non_air_region(&self) -> Option<Region> {
+        self.blocks().fold(None, |region, (pos, _)| match region {
+            Some(region) => Some(region.including(pos)),
+            None => Some(Region::new(pos, pos)),
+        })
+    }
+
+    /// Sets every block in `region` to `block`, growing the world to fit it.
+    pub fn fill(&mut self, region: Region, block: u16) {
+        let (min, max) = (region.min, region.max);
+        for sy in (min.y >> 4)..=(max.y >> 4) {
+            for sz in (min.z >> 4)..=(max.z >> 4) {
+                for sx in (min.x >> 4)..=(max.x >> 4) {
+                    let corner = BlockPos::new(sx * 16, sy * 16, sz * 16);
+                    let far = corner.offset(15, 15, 15);
+                    // Sections the region covers completely don't need to be set block by block
+                    if region.contains(corner) && region.contains(far) {
+                        self.fill_section((sx, sy, sz), block);
+                        continue;
+                    }
+                    for y in min.y.max(corner.y)..=max.y.min(far.y) {
+                        for z in min.z.max(corner.z)..=max.z.min(far.z) {
+                            for x in min.x.max(corner.x)..=max.x.min(far.x) {
+                                self.set_block(BlockPos::new(x, y, z), block);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        let inside = self
+            .block_entities()
+            .map(|(pos, _)| pos)
+            .filter(|&pos| region.contains(pos))
+            .collect::<Vec<_>>();
+        for pos in inside {
+            self.remove_block_entity(pos);
+        }
+    }
+
+    /// Replaces every block in `region` that `mask` matches with `to`, returning how many
+    /// blocks were replaced. See `BlockState::matches` for how masks work.
+    pub fn replace(&mut self, region: Region, mask: &BlockState, to: u16) -> usize {
+        let matching = self
+            .palette()
+            .iter()
+            .map(|state| state.matches(mask))
+            .collect::<Vec<_>>();
+        let mut positions = Vec::new();
+        self.for_each_block(region.min, region.size(), |pos, block| {
+            if matching[block as usize] && block != to {
+                positions.push(pos);
+            }
+        });
+        for &pos in &positions {
+            self.set_block(pos, to);
+            self.remove_block_entity(pos);
+        }
+        positions.len()
+    }
+
+    /// Copies `region` into a new world with the region's lowest corner at the origin, so
+    /// pasting it at a position puts that corner there. Air is copied as well, and the new
+    /// world always spans the whole region.
+    pub fn copy(&self, region: Region) -> World {
+        let (sx, sy, sz) = region.size();
+        let mut world = World::new(sx, sy, sz);
+        for (pos, entity) in self.block_entities() {
+            if region.contains(pos) {
+                world.set_block_entity(pos - region.min, entity.clone());
+            }
+        }
+        let remap = self
+            .palette()
+            .iter()
+            .map(|state| world.add_block_state(state))
+            .collect::<Vec<_>>();
+        self.for_each_block(region.min, region.size(), |pos, block| {
+            world.set_block(pos - region.min, remap[block as usize]);
+        });
+        world
+    }
+
+    /// Repeats `region` `count` more times in `dir`, leaving `spacing` blocks between each
+    /// copy. Like WorldEdit's `//stack`, air is copied too and replaces whatever was there.
+    pub fn stack(&mut self, region: Region, dir: BlockDirection, count: usize, spacing: i32) {
+        let copy = self.copy(region);
+        let step = region.length(dir) + spacing;
+        for i in 1..=count as i32 {
+            let at = region.offset_dir(dir, i * step).min;
+            self.paste(&copy, at, PasteOptions::default());
+        }
+    }
+}
@@ -0,0 +1,401 @@
+//! Finding paths for wires around whatever is already in the world
+
+use crate::basic::Routed;
+use crate::block_state::BlockState;
+use crate::error::{Error, Result};
+use crate::region::Region;
+use crate::world::{BlockDirection, BlockPos, World};
+use std::cmp::Reverse;
+use std::collections::hash_map::Entry;
+use std::collections::{BinaryHeap, HashMap};
+
+/// Blocks that a wire can't pass next to without interfering with them or picking up their
+/// signal.
+const COMPONENTS: [&str; 20] = [
+    "redstone_wire",
+    "repeater",
+    "comparator",
+    "redstone_torch",
+    "redstone_wall_torch",
+    "redstone_block",
+    "observer",
+    "target",
+    "lever",
+    "piston",
+    "sticky_piston",
+    "redstone_lamp",
+    "dispenser",
+    "dropper",
+    "hopper",
+    "note_block",
+    "daylight_detector",
+    "tripwire_hook",
+    "stone_button",
+    "stone_pressure_plate",
+];
+
+/// Extra cost of a repeater over a wire, so that routes don't add delay they don't need.
+const REPEATER_COST: u32 = 2;
+
+/// Limits on how far `World::route` searches.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RouteOptions {
+    /// How far past the box between the two ends the route may go
+    pub margin: i32,
+    /// How many steps to try before giving up
+    pub max_nodes: usize,
+}
+
+impl Default for RouteOptions {
+    fn default() -> Self {
+        Self {
+            margin: 8,
+            max_nodes: 200_000,
+        }
+    }
+}
+
+/// Where the search is, along with what the next step is allowed to do.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct State {
+    /// The wire or repeater, which sits on a block below it
+    pos: BlockPos,
+    /// The direction the signal came in from the previous step
+    dir: Option<BlockDirection>,
+    /// Strength of the signal here. Repeaters always output 15.
+    ss: u8,
+    repeater: bool,
+}
+
+struct Node {
+    state: State,
+    parent: Option<usize>,
+    cost: u32,
+}
+
+impl World {
+    /// Connects a wire at `from` to a wire at `to`, with `block` below every wire, going around
+    /// everything already in the world. Wires keep at least one block away from other redstone
+    /// components, except at the two ends, and repeaters are added wherever the signal would
+    /// otherwise run out. `from` is assumed to be powered at full strength. Fails with
+    /// `Error::NoRoute` if there's no room for a route within `options`, in which case the world
+    /// is left as it was. In very tight spaces it can also fail when a route exists, see
+    /// `find_route`.
+    pub fn route(
+        &mut self,
+        block: u16,
+        from: BlockPos,
+        to: BlockPos,
+        options: RouteOptions,
+    ) -> Result<Routed> {
+        let path = self
+            .find_route(from, to, options, &[])
+            .ok_or(Error::NoRoute { from, to })?;
+        Ok(self.place_route(block, &path))
+    }
+
+    /// Routes a bus, connecting every port in `from` to the port at the same index in `to`.
+    /// Bits are routed one at a time, so bits that swap places cross by going over or under
+    /// the ones routed before them. Afterwards, every bit is slowed down to the delay of the
+    /// slowest one, first by raising the delay of its repeaters and then by turning straight
+    /// pieces of wire into repeaters. Fails with `Error::NoRoute` for the first bit that can't
+    /// be routed, or `Error::DelayMismatch` for the first one that can't be slowed down enough.
+    /// The bus is built on a copy of the world, so nothing is placed unless every bit succeeds.
+    pub fn route_bus(
+        &mut self,
+        block: u16,
+        from: &[BlockPos],
+        to: &[BlockPos],
+        options: RouteOptions,
+    ) -> Result<Vec<Routed>> {
+        assert_eq!(from.len(), to.len(), "buses need as many inputs as outputs");
+        let ports = from.iter().chain(to).copied().collect::<Vec<_>>();
+        let mut world = self.clone();
+        let mut paths = Vec::new();
+        let mut routes = Vec::new();
+        for (&from, &to) in from.iter().zip(to) {
+            // Keep clear of the ports of the bits that haven't been routed yet
+            let reserved = ports
+                .iter()
+                .copied()
+                .filter(|&port| port != from && port != to)
+                .collect::<Vec<_>>();
+            let path = world
+                .find_route(from, to, options, &reserved)
+                .ok_or(Error::NoRoute { from, to })?;
+            routes.push(world.place_route(block, &path));
+            paths.push(path);
+        }
+
+        let slowest = routes.iter().map(|route| route.delay).max().unwrap_or(0);
+        for (path, route) in paths.iter().zip(&mut routes) {
+            let mut delays = path
+                .iter()
+                .map(|state| state.repeater as u32)
+                .collect::<Vec<_>>();
+            let mut missing = slowest - route.delay;
+            // Existing repeaters go up to 4 ticks
+            for delay in delays.iter_mut().filter(|delay| **delay > 0) {
+                let extra = missing.min(4 - *delay);
+                *delay += extra;
+                missing -= extra;
+            }
+            // Wires where the signal goes straight through on one level can become repeaters
+            for i in 1..path.len().saturating_sub(1) {
+                let straight = path[i].dir == path[i + 1].dir
+                    && path[i - 1].pos.y == path[i].pos.y
+                    && path[i + 1].pos.y == path[i].pos.y;
+                if missing > 0 && delays[i] == 0 && straight {
+                    delays[i] = missing.min(4);
+                    missing -= delays[i];
+                }
+            }
+            if missing > 0 {
+                let (from, to) = (path[0].pos, path[path.len() - 1].pos);
+                return Err(Error::DelayMismatch { from, to, missing });
+            }
+
+            for (state, &delay) in path.iter().zip(&delays) {
+                if let (true, Some(dir)) = (delay > 0, state.dir) {
+                    let repeater = world.add_block_state(&repeater(dir, delay));
+                    world.set_block(state.pos, repeater);
+                }
+            }
+            route.delay = slowest;
+            // Wires that became repeaters restore the signal behind them
+            route.ss = (1..path.len()).fold(15, |ss, i| match delays[i - 1] {
+                0 => ss - 1,
+                _ => 15,
+            });
+        }
+        *self = world;
+        Ok(routes)
+    }
+
+    /// Places the wires, repeaters and blocks below them along `path`.
+    fn place_route(&mut self, block: u16, path: &[State]) -> Routed {
+        let wire = self.add_block("minecraft:redstone_wire");
+        let (from, to) = (path[0].pos, path[path.len() - 1].pos);
+        let mut footprint = Region::new(from, from);
+        let mut delay = 0;
+        for state in path {
+            let top = match (state.repeater, state.dir) {
+                (true, Some(dir)) => {
+                    delay += 1;
+                    self.add_block_state(&repeater(dir, 1))
+                }
+                _ => wire,
+            };
+            let below = state.pos.offset(0, -1, 0);
+            self.set_block(below, block);
+            self.set_block(state.pos, top);
+            footprint = footprint.including(below).including(state.pos);
+        }
+        Routed {
+            output: to,
+            delay,
+            inverted: false,
+            ss: path[path.len() - 1].ss as u32,
+            footprint,
+        }
+    }
+
+    /// Searches for the cheapest route with A*, returning every step from `from` to `to`.
+    /// `reserved` positions are treated like components that haven't been placed yet.
+    ///
+    /// Only the cheapest way to reach each state is kept, but whether a step runs into the
+    /// route behind it depends on the whole path. So when the cheapest way to a state blocks
+    /// every way on from there while a more expensive one wouldn't, the search can miss a route
+    /// that exists. Keeping whole paths in the state would make the search far bigger, and it
+    /// only matters where the route has to double back on itself.
+    fn find_route(
+        &self,
+        from: BlockPos,
+        to: BlockPos,
+        options: RouteOptions,
+        reserved: &[BlockPos],
+    ) -> Option<Vec<State>> {
+        let margin = options.margin;
+        let area = Region::new(from, to);
+        let area = Region::new(
+            area.min.offset(-margin, -margin, -margin),
+            area.max.offset(margin, margin, margin),
+        );
+        let components = self
+            .palette()
+            .iter()
+            .map(|state| state.namespace == "minecraft" && COMPONENTS.contains(&state.id.as_str()))
+            .collect::<Vec<_>>();
+        let free = |pos: BlockPos| {
+            area.contains(pos) && self.get_block(pos) == 0 && !reserved.contains(&pos)
+        };
+        let component =
+            |pos: BlockPos| components[self.get_block(pos) as usize] || reserved.contains(&pos);
+
+        // Whether a wire and the block below it fit at `pos`
+        let mut fits_cache = HashMap::new();
+        let mut fits = |pos: BlockPos| {
+            *fits_cache.entry(pos).or_insert_with(|| {
+                let below = pos.offset(0, -1, 0);
+                if !free(pos) || !free(below) {
+                    return false;
+                }
+                if pos == from || pos == to {
+                    return true;
+                }
+                let near_wire = BlockDirection::HORIZONTAL.iter().any(|&dir| {
+                    let side = pos.offset_dir(dir, 1);
+                    (-1..=1).any(|dy| component(side.offset(0, dy, 0)))
+                });
+                // The wire powers the block below it, which powers anything next to that
+                let near_below = BlockDirection::HORIZONTAL
+                    .iter()
+                    .any(|&dir| component(below.offset_dir(dir, 1)));
+                !near_wire
+                    && !near_below
+                    && !component(below.offset(0, -1, 0))
+                    && !component(pos.offset(0, 1, 0))
+            })
+        };
+
+        if !fits(from) || !fits(to) {
+            return None;
+        }
+        let heuristic = |pos: BlockPos| {
+            let d = to - pos;
+            ((d.x.abs() + d.z.abs()).max(d.y.abs())) as u32
+        };
+
+        let start = State {
+            pos: from,
+            dir: None,
+            ss: 15,
+            repeater: false,
+        };
+        let mut nodes = vec![Node {
+            state: start,
+            parent: None,
+            cost: 0,
+        }];
+        let mut best = HashMap::from([(start, 0)]);
+        let mut queue = BinaryHeap::from([Reverse((heuristic(from), 0))]);
+
+        while let Some(Reverse((_, idx))) = queue.pop() {
+            let Node { state, cost, .. } = nodes[idx];
+            if best.get(&state).is_some_and(|&best| best < cost) {
+                continue;
+            }
+            if state.pos == to && !state.repeater {
+                let mut path = Vec::new();
+                let mut cur = Some(idx);
+                while let Some(idx) = cur {
+                    path.push(nodes[idx].state);
+                    cur = nodes[idx].parent;
+                }
+                path.reverse();
+                return Some(path);
+            }
+            if nodes.len() > options.max_nodes {
+                return None;
+            }
+
+            for dir in BlockDirection::HORIZONTAL {
+                if state.dir == Some(dir.opposite()) {
+                    continue;
+                }
+                // A repeater's output has to go straight into a wire on the same level
+                if state.repeater && state.dir != Some(dir) {
+                    continue;
+                }
+                for dy in [0, 1, -1] {
+                    if state.repeater && dy != 0 {
+                        continue;
+                    }
+                    let next = state.pos.offset_dir(dir, 1).offset(0, dy, 0);
+                    // Going up or down a step needs air where the wire would otherwise be cut
+                    let gap = match dy {
+                        1 => Some(state.pos.offset(0, 1, 0)),
+                        -1 => Some(next.offset(0, 1, 0)),
+                        _ => None,
+                    };
+                    if !fits(next) || gap.is_some_and(|gap| !free(gap)) {
+                        continue;
+                    }
+                    if crosses_path(&nodes, idx, next, gap) {
+                        continue;
+                    }
+
+                    let ss = match state.repeater {
+                        true => 15,
+                        false => state.ss - 1,
+                    };
+                    let mut steps = Vec::new();
+                    if ss > 0 {
+                        steps.push((ss, false, 1));
+                    }
+                    // Repeaters need their input on the same level behind them
+                    if dy == 0 && next != to {
+                        steps.push((15, true, 1 + REPEATER_COST));
+                    }
+                    for (ss, repeater, step_cost) in steps {
+                        let next_state = State {
+                            pos: next,
+                            dir: Some(dir),
+                            ss,
+                            repeater,
+                        };
+                        let next_cost = cost + step_cost;
+                        match best.entry(next_state) {
+                            Entry::Occupied(entry) if *entry.get() <= next_cost => continue,
+                            Entry::Occupied(mut entry) => *entry.get_mut() = next_cost,
+                            Entry::Vacant(entry) => {
+                                entry.insert(next_cost);
+                            }
+                        }
+                        nodes.push(Node {
+                            state: next_state,
+                            parent: Some(idx),
+                            cost: next_cost,
+                        });
+                        let estimate = next_cost + heuristic(next);
+                        queue.push(Reverse((estimate, nodes.len() - 1)));
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+/// A repeater passing on a signal that travels in `dir`, taking `delay` redstone ticks.
+fn repeater(dir: BlockDirection, delay: u32) -> BlockState {
+    // Repeaters face towards their input
+    let repeater = BlockState::new("minecraft:repeater").with("facing", dir.opposite().name());
+    match delay {
+        1 => repeater,
+        _ => repeater.with("delay", delay),
+    }
+}
+
+/// Whether a wire at `pos` would run into, or connect sideways to, the route leading up to
+/// `idx`. `gap` is the air a step up or down needs, which also has to stay clear of the route.
+fn crosses_path(nodes: &[Node], idx: usize, pos: BlockPos, gap: Option<BlockPos>) -> bool {
+    let below = pos.offset(0, -1, 0);
+    let prev = nodes[idx].state.pos;
+    let mut cur = Some(idx);
+    while let Some(idx) = cur {
+        let wire = nodes[idx].state.pos;
+        let support = wire.offset(0, -1, 0);
+        for used in [wire, support] {
+            if used == pos || used == below || Some(used) == gap {
+                return true;
+            }
+        }
+        let d = pos - wire;
+        if wire != prev && d.x.abs() + d.z.abs() <= 1 && d.y.abs() <= 1 {
+            return true;
+        }
+        cur = nodes[idx].parent;
+    }
+    false
+}
@@ -1,9 +1,20 @@
 //! Generation for RISC-V standard compressed instruction-set (RVC) decoders
 
-use redstone_schem::basic::create_wire;
-use redstone_schem::world::{BlockPos, World};
-
-const CROSS_WIRE: &str = "minecraft:redstone_wire[north=side,east=side,west=side,south=side]";
+use redstone_schem::basic::{create_wire, WireOptions};
+use redstone_schem::block_state::BlockState;
+use redstone_schem::region::Region;
+use redstone_schem::world::{
+    BlockDirection, BlockPos, ExportArea, PasteOptions, SchematicVersion, World,
+};
+
+/// Wire connected on every side, so that it powers the blocks around it
+fn cross_wire() -> BlockState {
+    ["north", "east", "west", "south"]
+        .into_iter()
+        .fold(BlockState::new("minecraft:redstone_wire"), |wire, side| {
+            wire.with(side, "side")
+        })
+}
 
 // Get position of wire with correct grouping
 fn byte_pos(mut pos: BlockPos) -> BlockPos {
@@ -13,14 +24,13 @@ fn byte_pos(mut pos: BlockPos) -> BlockPos {
     pos
 }
 
-fn wire_block(world: &mut World, mut pos: BlockPos, block: u16) {
-    let wire = world.add_block(CROSS_WIRE);
+fn wire_block(world: &mut World, pos: BlockPos, block: u16) {
+    let wire = world.add_block_state(&cross_wire());
     world.set_block(pos, block);
-    pos.y += 1;
-    world.set_block(pos, wire);
+    world.set_block(pos.offset_dir(BlockDirection::Up, 1), wire);
 }
 
-fn tower(world: &mut World, start: BlockPos, height: usize) {
+fn tower(world: &mut World, start: BlockPos, height: i32) {
     if height == 0 {
         return;
     }
@@ -72,16 +82,51 @@ fn tower(world: &mut World, start: BlockPos, height: usize) {
     }
 }
 
-fn bus(world: &mut World, start: BlockPos, bits: usize, length: usize, repeated: bool) {
-    let concrete = world.add_block("minecraft:gray_concrete");
-    for i in 0..bits {
-        let start = byte_pos(start.offset(0, i as isize * 2, 0));
-        let end = start.offset(length as isize, 0, 0);
-        create_wire(world, concrete, start, end, repeated);
+fn bus(world: &mut World, start: BlockPos, bits: i32, length: i32, repeated: bool) {
+    if bits <= 0 {
+        return;
     }
+    // Built on its own so that stacking doesn't copy air over what's already there
+    let mut bus = World::default();
+    let concrete = bus.add_block("minecraft:gray_concrete");
+    let origin = BlockPos::default();
+    create_wire(
+        &mut bus,
+        concrete,
+        origin,
+        origin.offset(length, 0, 0),
+        WireOptions {
+            repeated,
+            // Buses without repeaters don't have to carry the signal all the way
+            min_output: repeated as u32,
+            ..Default::default()
+        },
+    )
+    .expect("failed to draw bus");
+
+    // Bits are every other block, with each byte 20 blocks above the last
+    let bit = Region::new(origin, origin.offset(length, 1, 0));
+    bus.stack(bit, BlockDirection::Up, (bits.min(8) - 1) as usize, 0);
+    let options = PasteOptions {
+        skip_air: true,
+        ..Default::default()
+    };
+    let bytes = bits / 8;
+    if bytes > 1 {
+        let byte = Region::new(origin, origin.offset(length, 15, 0));
+        bus.stack(byte, BlockDirection::Up, (bytes - 1) as usize, 4);
+    }
+    // Bits past the last whole byte start a byte of their own
+    let rest = bits % 8;
+    if bytes > 0 && rest > 0 {
+        let rest = bus.copy(Region::new(origin, origin.offset(length, rest * 2 - 1, 0)));
+        bus.paste(&rest, byte_pos(origin.offset(0, bytes * 16, 0)), options);
+    }
+
+    world.paste(&bus, byte_pos(start), options);
 }
 
-fn connect_bits(world: &mut World, x: usize, a: usize, b: usize) {
+fn connect_bits(world: &mut World, x: i32, a: i32, b: i32) {
     let concrete = world.add_block("minecraft:gray_concrete");
     let repeater = world.add_block("minecraft:repeater[facing=north]");
     let target = world.add_block("minecraft:target");
@@ -144,7 +189,7 @@ fn connect_bit_range(
         println!("{} -> {}", a, b);
         let slot = *bit_slot[a..=b].iter().max().unwrap();
 
-        connect_bits(world, slot * 2, a, b);
+        connect_bits(world, slot as i32 * 2, a as i32, b as i32);
 
         bit_slot[a..=b].iter_mut().for_each(|s| *s = slot + 1);
     }
@@ -152,13 +197,13 @@ fn connect_bit_range(
 
 fn extend_bit(world: &mut World, bit_slot: &mut [usize], bit: usize, start: usize, end: usize) {
     let slot = *bit_slot[bit..=end].iter().max().unwrap();
-    connect_bits(world, slot * 2, bit, end);
+    connect_bits(world, slot as i32 * 2, bit as i32, end as i32);
 
     let concrete = world.add_block("minecraft:gray_concrete");
     let repeater = world.add_block("minecraft:repeater[facing=north]");
     let slab = world.add_block("minecraft:smooth_stone_slab[type=top]");
     for b in start + 1..end {
-        let pos = byte_pos(BlockPos::new(slot * 2, b * 2, 6));
+        let pos = byte_pos(BlockPos::new(slot as i32 * 2, b as i32 * 2, 6));
         world.set_block(pos, concrete);
         world.set_block(pos.offset(0, 1, 0), repeater);
         if b % 16 == 1 {
@@ -183,7 +228,7 @@ fn constant_range(world: &mut World, start: usize, constant: u32) {
         let b = constant & (1 << i);
         if b > 0 {
             println!("#1 -> {}", start + i);
-            let pos = BlockPos::new(0, (start + i) * 2 + 1, 6);
+            let pos = BlockPos::new(0, (start + i) as i32 * 2 + 1, 6);
             world.set_block(byte_pos(pos), redstone_block);
         }
     }
@@ -193,12 +238,12 @@ fn gen_ins<F>(name: &str, f: F)
 where
     F: FnOnce(&mut World, &mut [usize; 32]),
 {
-    let mut world = World::new(48, 76, 10);
+    let mut world = World::default();
     let mut bit_slot = [0; 32];
 
     f(&mut world, &mut bit_slot);
 
-    let length = *bit_slot.iter().max().unwrap() * 2;
+    let length = *bit_slot.iter().max().unwrap() as i32 * 2;
 
     let concrete = world.add_block("minecraft:gray_concrete");
     let wall_torch = world.add_block("minecraft:redstone_wall_torch[facing=south]");
@@ -225,8 +270,12 @@ where
                 concrete,
                 pos.offset(0, 3, -1),
                 pos.offset(0, 3, 1),
-                false,
-            );
+                WireOptions {
+                    repeated: false,
+                    ..Default::default()
+                },
+            )
+            .expect("failed to draw layer repeater");
             world.set_block(pos.offset(0, 4, 2), concrete);
             world.set_block(pos.offset(0, 5, 2), torch);
         }
@@ -241,7 +290,15 @@ where
     //     world.set_block(byte_pos(BlockPos::new(length, i * 2 + 1, 0)), repeater);
     // }
 
-    world.save_schematic(&format!("rvc/rvc_{}.schem", name), 0, -75, -9);
+    // Pasting puts the player at (0, 75, 9) in the decoder
+    world.translate(0, -75, -9);
+    world
+        .save_schematic(
+            &format!("rvc/rvc_{}.schem", name),
+            SchematicVersion::V2,
+            ExportArea::Crop,
+        )
+        .expect("failed to save schematic");
 }
 
 pub fn gen_rvc() {
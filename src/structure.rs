@@ -0,0 +1,78 @@
+//! Export to the vanilla structure block format
+
+use crate::error::Result;
+use crate::world::{PaletteEntry, World, MC_DATA_VERSION};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Write;
+
+impl World {
+    /// Saves the world as a structure that can be loaded with a structure block or
+    /// `/place template`. If `include_air` is false, air is left out of the structure so that
+    /// placing it doesn't clear whatever was already there.
+    pub fn save_structure(&self, file_name: &str, include_air: bool) -> Result<()> {
+        let data = self.structure_data(include_air)?;
+        let mut file = File::create(file_name)?;
+        file.write_all(&data)?;
+        Ok(())
+    }
+
+    pub fn structure_data(&self, include_air: bool) -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+        self.check_size(i32::MAX as usize)?;
+        let (sx, sy, sz) = self.size();
+        let min = self.min();
+
+        let mut block_entities = HashMap::new();
+        for (pos, entity) in self.block_entities() {
+            let mut blob = nbt::Blob::new();
+            for (key, value) in entity.to_compound() {
+                blob.insert(key, value)?;
+            }
+            block_entities.insert(pos, blob);
+        }
+
+        let mut blocks = Vec::new();
+        self.for_each_block(min, (sx, sy, sz), |pos, block| {
+            if block == 0 && !include_air {
+                return;
+            }
+            let local = pos - min;
+            blocks.push(StructureBlock {
+                state: block as i32,
+                pos: vec![local.x, local.y, local.z],
+                nbt: block_entities.remove(&pos),
+            });
+        });
+
+        let structure = Structure {
+            data_version: MC_DATA_VERSION,
+            size: vec![sx as i32, sy as i32, sz as i32],
+            palette: self.palette_entries(),
+            blocks,
+            entities: Vec::new(),
+        };
+        nbt::to_gzip_writer(&mut out, &structure, None)?;
+
+        Ok(out)
+    }
+}
+
+#[derive(Serialize)]
+struct StructureBlock {
+    state: i32,
+    pos: Vec<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    nbt: Option<nbt::Blob>,
+}
+
+#[derive(Serialize)]
+struct Structure {
+    #[serde(rename = "DataVersion")]
+    data_version: i32,
+    size: Vec<i32>,
+    palette: Vec<PaletteEntry>,
+    blocks: Vec<StructureBlock>,
+    entities: Vec<nbt::Blob>,
+}
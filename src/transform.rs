@@ -0,0 +1,191 @@
+//! Rotating and mirroring worlds along with the states of the blocks in them
+
+use crate::block_state::BlockState;
+use crate::world::{BlockDirection, BlockPos, World};
+
+/// Every rail shape other than the ascending ones, as the game names them
+const RAIL_SHAPES: [&str; 6] = [
+    "north_south",
+    "east_west",
+    "south_east",
+    "south_west",
+    "north_west",
+    "north_east",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Axis {
+    X,
+    Y,
+    Z,
+}
+
+impl World {
+    /// Rotates the world clockwise, as seen from above, by `quarter_turns` times 90 degrees.
+    /// Negative turns rotate counterclockwise. The bounds keep their lowest corner.
+    pub fn rotate(&mut self, quarter_turns: i32) {
+        let turns = quarter_turns.rem_euclid(4);
+        let size = self.size();
+        let (sx, _, sz) = (size.0 as i32, size.1 as i32, size.2 as i32);
+        let size = match turns {
+            1 | 3 => (size.2, size.1, size.0),
+            _ => size,
+        };
+        self.transform(
+            size,
+            |state| state.rotate(turns),
+            |pos| match turns {
+                1 => BlockPos::new(sz - 1 - pos.z, pos.y, pos.x),
+                2 => BlockPos::new(sx - 1 - pos.x, pos.y, sz - 1 - pos.z),
+                3 => BlockPos::new(pos.z, pos.y, sx - 1 - pos.x),
+                _ => pos,
+            },
+        );
+    }
+
+    /// Flips the world along `axis`, so `Axis::X` swaps east and west, `Axis::Y` swaps up and down
+    /// and `Axis::Z` swaps north and south. The world stays within the same bounds.
+    pub fn mirror(&mut self, axis: Axis) {
+        let size = self.size();
+        let (sx, sy, sz) = (size.0 as i32, size.1 as i32, size.2 as i32);
+        self.transform(
+            size,
+            |state| state.mirror(axis),
+            |pos| match axis {
+                Axis::X => BlockPos::new(sx - 1 - pos.x, pos.y, pos.z),
+                Axis::Y => BlockPos::new(pos.x, sy - 1 - pos.y, pos.z),
+                Axis::Z => BlockPos::new(pos.x, pos.y, sz - 1 - pos.z),
+            },
+        );
+    }
+
+    /// Rebuilds the world with a new size, moving every block to `move_pos` and replacing its
+    /// state with `map_state`. Positions passed to `move_pos` are relative to the lowest corner.
+    fn transform<S, P>(&mut self, size: (usize, usize, usize), map_state: S, move_pos: P)
+    where
+        S: Fn(&BlockState) -> BlockState,
+        P: Fn(BlockPos) -> BlockPos,
+    {
+        let min = self.min();
+        let mut world = World::new(size.0, size.1, size.2);
+        for (pos, entity) in self.block_entities() {
+            world.set_block_entity(move_pos(pos - min), entity.clone());
+        }
+        let remap = self
+            .palette()
+            .iter()
+            .map(|state| world.add_block_state(&map_state(state)))
+            .collect::<Vec<_>>();
+
+        self.for_each_block(min, self.size(), |pos, block| {
+            world.set_block(move_pos(pos - min), remap[block as usize]);
+        });
+        world.translate(min.x, min.y, min.z);
+        *self = world;
+    }
+}
+
+impl BlockState {
+    /// Rotates the directional properties of the state clockwise by `quarter_turns`.
+    pub fn rotate(&self, quarter_turns: i32) -> BlockState {
+        let turns = quarter_turns.rem_euclid(4) as usize;
+        let mut state = self.clone();
+        state.properties.clear();
+        for (key, value) in &self.properties {
+            let value = match key.as_str() {
+                "facing" => rotate_dir(value, turns).to_owned(),
+                // Stair shapes are relative to their facing, so only rails change
+                "shape" => map_rail_shape(value, |dir| rotate_dir(dir, turns))
+                    .unwrap_or_else(|| value.clone()),
+                "axis" if turns % 2 == 1 => match value.as_str() {
+                    "x" => "z".to_owned(),
+                    "z" => "x".to_owned(),
+                    _ => value.clone(),
+                },
+                // Signs and banners have 16 rotations
+                "rotation" => match value.parse::<usize>() {
+                    Ok(rotation) => ((rotation + turns * 4) % 16).to_string(),
+                    Err(_) => value.clone(),
+                },
+                _ => value.clone(),
+            };
+            // Wires, fences and panes store their connections by side
+            state.set_property(rotate_dir(key, turns), value);
+        }
+        state
+    }
+
+    /// Mirrors the directional properties of the state along `axis`.
+    pub fn mirror(&self, axis: Axis) -> BlockState {
+        // Mirroring horizontally turns anything with a handedness into its opposite
+        let flip_side = |value: &str| match axis {
+            Axis::Y => value.to_owned(),
+            _ if value.contains("left") => value.replace("left", "right"),
+            _ => value.replace("right", "left"),
+        };
+
+        let mut state = self.clone();
+        state.properties.clear();
+        for (key, value) in &self.properties {
+            let value = match (key.as_str(), value.as_str()) {
+                ("facing", _) => flip_dir(value, axis).to_owned(),
+                ("shape", _) => map_rail_shape(value, |dir| flip_dir(dir, axis))
+                    .unwrap_or_else(|| flip_side(value)),
+                // Chests use `type` to tell which half of a double chest they are
+                ("hinge", _) | ("type", "left" | "right") => flip_side(value),
+                // Slabs use `type`, stairs and trapdoors use `half`
+                ("type" | "half", "top") if axis == Axis::Y => "bottom".to_owned(),
+                ("type" | "half", "bottom") if axis == Axis::Y => "top".to_owned(),
+                ("face", "floor") if axis == Axis::Y => "ceiling".to_owned(),
+                ("face", "ceiling") if axis == Axis::Y => "floor".to_owned(),
+                ("rotation", _) => match (axis, value.parse::<usize>()) {
+                    (Axis::X, Ok(rotation)) => ((16 - rotation) % 16).to_string(),
+                    (Axis::Z, Ok(rotation)) => ((24 - rotation) % 16).to_string(),
+                    _ => value.clone(),
+                },
+                _ => value.clone(),
+            };
+            // `up` on walls is whether they have a post rather than a side, so only horizontal
+            // sides are swapped in property names
+            match axis {
+                Axis::Y => state.set_property(key, value),
+                _ => state.set_property(flip_dir(key, axis), value),
+            }
+        }
+        state
+    }
+}
+
+/// Moves the sides a rail connects to with `map_dir`, or returns `None` if `shape` isn't a rail
+/// shape. Stairs also have a `shape`, but theirs never name a direction.
+fn map_rail_shape(shape: &str, map_dir: impl Fn(&str) -> &str) -> Option<String> {
+    if let Some(dir) = shape.strip_prefix("ascending_") {
+        return Some(format!("ascending_{}", map_dir(dir)));
+    }
+    let (a, b) = shape.split_once('_')?;
+    let (a, b) = (map_dir(a), map_dir(b));
+    // The game only accepts the sides in one order
+    RAIL_SHAPES
+        .iter()
+        .find(|&&name| name == format!("{}_{}", a, b) || name == format!("{}_{}", b, a))
+        .map(|&name| name.to_owned())
+}
+
+fn rotate_dir(dir: &str, quarter_turns: usize) -> &str {
+    match BlockDirection::from_name(dir) {
+        Some(dir) => dir.rotate(quarter_turns as i32).name(),
+        None => dir,
+    }
+}
+
+fn flip_dir(dir: &str, axis: Axis) -> &str {
+    match (axis, dir) {
+        (Axis::X, "east") => "west",
+        (Axis::X, "west") => "east",
+        (Axis::Y, "up") => "down",
+        (Axis::Y, "down") => "up",
+        (Axis::Z, "north") => "south",
+        (Axis::Z, "south") => "north",
+        _ => dir,
+    }
+}
@@ -0,0 +1,46 @@
+//! The VarInt encoding used for block data in Sponge schematics. Each byte holds 7 bits of the
+//! value, least significant first, and the high bit is set on every byte but the last.
+
+use crate::error::{Error, Result};
+
+/// The most bytes a 32-bit value can take up
+const MAX_LEN: usize = 5;
+
+/// Appends `value` to `out`.
+pub fn encode(mut value: u32, out: &mut Vec<i8>) {
+    loop {
+        let mut byte = (value & 0b0111_1111) as u8;
+        value >>= 7;
+        if value != 0 {
+            byte |= 0b1000_0000;
+        }
+        out.push(byte as i8);
+        if value == 0 {
+            break;
+        }
+    }
+}
+
+/// Decodes every value in `data`, which must end on a complete value.
+pub fn decode(data: &[i8]) -> Result<Vec<u32>> {
+    let mut values = Vec::new();
+    let mut value = 0;
+    let mut len = 0;
+    for &byte in data {
+        let byte = byte as u8;
+        if len == MAX_LEN - 1 && byte > 0b1111 {
+            return Err(Error::InvalidSchematic("varint is too long".to_owned()));
+        }
+        value |= ((byte & 0b0111_1111) as u32) << (len * 7);
+        len += 1;
+        if byte & 0b1000_0000 == 0 {
+            values.push(value);
+            value = 0;
+            len = 0;
+        }
+    }
+    if len != 0 {
+        return Err(Error::InvalidSchematic("varint is truncated".to_owned()));
+    }
+    Ok(values)
+}
@@ -1,8 +1,14 @@
-use serde::Serialize;
+use crate::block_entity::BlockEntity;
+use crate::block_state::BlockState;
+use crate::error::{Error, Result};
+use crate::region::Region;
+use crate::varint;
+use serde::{Serialize, Serializer};
 use std::cmp::Ordering;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs::File;
-use std::io::Write;
+use std::io::{BufReader, Read, Write};
+use std::ops::{Add, Sub};
 
 pub const MC_DATA_VERSION: i32 = 2730;
 
@@ -17,189 +23,850 @@ macro_rules! map(
         }
      };
 );
+pub(crate) use map;
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
 pub struct BlockPos {
-    pub x: usize,
-    pub y: usize,
-    pub z: usize,
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
 }
 
 impl BlockPos {
-    pub fn new(x: usize, y: usize, z: usize) -> Self {
+    pub fn new(x: i32, y: i32, z: i32) -> Self {
         Self { x, y, z }
     }
 
-    pub fn direction_to(self, other: BlockPos) -> BlockDirection {
-        match (self.x.cmp(&other.x), self.z.cmp(&other.z)) {
-            (Ordering::Greater, _) => BlockDirection::West,
-            (Ordering::Less, _) => BlockDirection::East,
-            (_, Ordering::Greater) => BlockDirection::North,
-            (_, Ordering::Less) => BlockDirection::South,
-            _ => unreachable!(),
-        }
+    /// The direction to step in to get closer to `other`, preferring x, then z, then y. Returns
+    /// `None` if the positions are the same.
+    pub fn direction_to(self, other: BlockPos) -> Option<BlockDirection> {
+        let dir = match (
+            self.x.cmp(&other.x),
+            self.z.cmp(&other.z),
+            self.y.cmp(&other.y),
+        ) {
+            (Ordering::Greater, _, _) => BlockDirection::West,
+            (Ordering::Less, _, _) => BlockDirection::East,
+            (_, Ordering::Greater, _) => BlockDirection::North,
+            (_, Ordering::Less, _) => BlockDirection::South,
+            (_, _, Ordering::Greater) => BlockDirection::Down,
+            (_, _, Ordering::Less) => BlockDirection::Up,
+            _ => return None,
+        };
+        Some(dir)
     }
 
-    pub fn offset(self, x: isize, y: isize, z: isize) -> Self {
+    pub fn offset(self, x: i32, y: i32, z: i32) -> Self {
         Self {
-            x: (self.x as isize + x) as usize,
-            y: (self.y as isize + y) as usize,
-            z: (self.z as isize + z) as usize,
+            x: self.x + x,
+            y: self.y + y,
+            z: self.z + z,
         }
     }
 
-    pub fn offset_dir(self, dir: BlockDirection, amt: isize) -> BlockPos {
+    pub fn offset_dir(self, dir: BlockDirection, amt: i32) -> BlockPos {
         match dir {
             BlockDirection::West => self.offset(-amt, 0, 0),
             BlockDirection::East => self.offset(amt, 0, 0),
             BlockDirection::North => self.offset(0, 0, -amt),
             BlockDirection::South => self.offset(0, 0, amt),
+            BlockDirection::Up => self.offset(0, amt, 0),
+            BlockDirection::Down => self.offset(0, -amt, 0),
         }
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+impl Add for BlockPos {
+    type Output = BlockPos;
+
+    fn add(self, other: BlockPos) -> BlockPos {
+        self.offset(other.x, other.y, other.z)
+    }
+}
+
+impl Sub for BlockPos {
+    type Output = BlockPos;
+
+    fn sub(self, other: BlockPos) -> BlockPos {
+        self.offset(-other.x, -other.y, -other.z)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum BlockDirection {
     North,
     South,
     East,
     West,
+    Up,
+    Down,
+}
+
+impl BlockDirection {
+    /// Horizontal directions in clockwise order, as seen from above
+    pub const HORIZONTAL: [BlockDirection; 4] = [
+        BlockDirection::North,
+        BlockDirection::East,
+        BlockDirection::South,
+        BlockDirection::West,
+    ];
+
+    pub fn opposite(self) -> BlockDirection {
+        match self {
+            BlockDirection::North => BlockDirection::South,
+            BlockDirection::South => BlockDirection::North,
+            BlockDirection::East => BlockDirection::West,
+            BlockDirection::West => BlockDirection::East,
+            BlockDirection::Up => BlockDirection::Down,
+            BlockDirection::Down => BlockDirection::Up,
+        }
+    }
+
+    /// Rotates clockwise, as seen from above, by `quarter_turns` times 90 degrees. Up and down
+    /// stay the same.
+    pub fn rotate(self, quarter_turns: i32) -> BlockDirection {
+        match Self::HORIZONTAL.iter().position(|&dir| dir == self) {
+            Some(i) => Self::HORIZONTAL[(i as i32 + quarter_turns).rem_euclid(4) as usize],
+            None => self,
+        }
+    }
+
+    pub fn is_horizontal(self) -> bool {
+        !matches!(self, BlockDirection::Up | BlockDirection::Down)
+    }
+
+    /// The name used for this direction in block states, like the `facing` of a repeater.
+    pub fn name(self) -> &'static str {
+        match self {
+            BlockDirection::North => "north",
+            BlockDirection::South => "south",
+            BlockDirection::East => "east",
+            BlockDirection::West => "west",
+            BlockDirection::Up => "up",
+            BlockDirection::Down => "down",
+        }
+    }
+
+    /// Parses a block state direction like `north`.
+    pub fn from_name(name: &str) -> Option<BlockDirection> {
+        let dir = match name {
+            "north" => BlockDirection::North,
+            "south" => BlockDirection::South,
+            "east" => BlockDirection::East,
+            "west" => BlockDirection::West,
+            "up" => BlockDirection::Up,
+            "down" => BlockDirection::Down,
+            _ => return None,
+        };
+        Some(dir)
+    }
+}
+
+/// Which revision of the Sponge schematic format to write.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SchematicVersion {
+    #[default]
+    V2,
+    /// Nests the palette, block data and block entities in a `Blocks` container and stores the
+    /// paste offset in `Offset` instead of WorldEdit's metadata.
+    V3,
+}
+
+/// Which part of the world `World::save_schematic` exports.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ExportArea {
+    /// Everything within the world's bounds, including any empty space around the edges.
+    #[default]
+    Bounds,
+    /// Only the smallest box around every block that isn't air.
+    Crop,
+    /// Only the given region, which doesn't have to be inside the world.
+    Region(Region),
 }
 
+/// How `World::paste` treats air and the blocks already in the destination.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PasteOptions {
+    /// Leaves the destination untouched wherever the source has air.
+    pub skip_air: bool,
+    /// Only places blocks where the destination has air.
+    pub only_replace_air: bool,
+}
+
+/// A growable box of blocks. Positions can be anywhere, including below zero, and the world
+/// keeps track of the bounding box of every position that was set so that exporters know what
+/// to save. Blocks are stored in 16x16x16 sections that are only allocated once something other
+/// than air is placed in them, so mostly empty builds stay cheap.
+#[derive(Clone)]
 pub struct World {
-    sx: usize,
-    sy: usize,
-    sz: usize,
-    data: Vec<u16>,
-    palette: HashMap<&'static str, u16>,
-    barrels: HashMap<BlockPos, u32>,
+    sections: HashMap<(i32, i32, i32), Section>,
+    /// Lowest and highest corner of every position that was set
+    bounds: Option<(BlockPos, BlockPos)>,
+    palette: Vec<BlockState>,
+    palette_ids: HashMap<BlockState, u16>,
+    block_entities: HashMap<BlockPos, BlockEntity>,
+}
+
+impl Default for World {
+    fn default() -> Self {
+        World::new(0, 0, 0)
+    }
 }
 
 impl World {
+    /// Creates a world that already spans from the origin to `sx`, `sy`, `sz`, so that it is
+    /// exported at that size even if the edges are left empty. Use `World::default` to start
+    /// out empty.
     pub fn new(sx: usize, sy: usize, sz: usize) -> Self {
-        let mut palette = HashMap::new();
-        palette.insert("minecraft:air", 0);
+        let air = BlockState::new("minecraft:air");
+        let bounds = (sx > 0 && sy > 0 && sz > 0).then(|| {
+            let max = BlockPos::new(sx as i32 - 1, sy as i32 - 1, sz as i32 - 1);
+            (BlockPos::default(), max)
+        });
         Self {
-            sx,
-            sy,
-            sz,
-            data: vec![0; sx * sy * sz],
-            palette,
-            barrels: HashMap::new(),
+            sections: HashMap::new(),
+            bounds,
+            palette: vec![air.clone()],
+            palette_ids: HashMap::from([(air, 0)]),
+            block_entities: HashMap::new(),
+        }
+    }
+
+    /// Like `try_add_block`, but panics if `name` isn't a valid block state.
+    pub fn add_block(&mut self, name: &str) -> u16 {
+        match self.try_add_block(name) {
+            Ok(block) => block,
+            Err(err) => panic!("add_block failed: {}", err),
         }
     }
 
-    pub fn add_block(&mut self, name: &'static str) -> u16 {
-        let new = self.palette.len();
-        *self.palette.entry(name).or_insert(new as u16)
+    /// Parses a block state like `minecraft:repeater[facing=south]` and adds it to the palette.
+    pub fn try_add_block(&mut self, name: &str) -> Result<u16> {
+        self.try_add_block_state(&name.parse()?)
     }
 
-    pub fn set_block(&mut self, pos: BlockPos, block: u16) {
-        if pos.x >= self.sx || pos.y >= self.sy || pos.z >= self.sz {
-            panic!("out of bounds set_block to {:?}", pos);
+    /// Like `try_add_block_state`, but panics if the palette is full.
+    pub fn add_block_state(&mut self, state: &BlockState) -> u16 {
+        match self.try_add_block_state(state) {
+            Ok(block) => block,
+            Err(err) => panic!("add_block_state failed: {}", err),
+        }
+    }
+
+    /// Returns the palette id of `state`, adding it to the palette if it's new. Fails if the
+    /// palette already holds every id a `u16` can store.
+    pub fn try_add_block_state(&mut self, state: &BlockState) -> Result<u16> {
+        if let Some(&id) = self.palette_ids.get(state) {
+            return Ok(id);
+        }
+        let new = u16::try_from(self.palette.len()).map_err(|_| Error::PaletteFull)?;
+        self.palette.push(state.clone());
+        self.palette_ids.insert(state.clone(), new);
+        Ok(new)
+    }
+
+    /// Stores a block without touching the bounds.
+    fn put_block(&mut self, pos: BlockPos, block: u16) {
+        let (key, idx) = section_index(pos);
+        match self.sections.get_mut(&key) {
+            Some(section) => {
+                section.set(idx, block);
+                // Sections that are back to air are freed
+                if let Section::Uniform(0) = section {
+                    self.sections.remove(&key);
+                }
+            }
+            // Sections start out as air, so there's nothing to do
+            None if block == 0 => {}
+            None => {
+                let mut section = Section::Uniform(0);
+                section.set(idx, block);
+                self.sections.insert(key, section);
+            }
         }
-        let idx = (self.sx * self.sy * pos.z) + (self.sx * pos.y) + pos.x;
-        self.data[idx] = block;
     }
 
+    /// Sets the block at `pos`, growing the world if `pos` is outside of it.
+    pub fn set_block(&mut self, pos: BlockPos, block: u16) {
+        self.put_block(pos, block);
+        self.grow_bounds(pos, pos);
+    }
+
+    /// Sets every block in the 16x16x16 section at `key`, as in `section_index`, to `block`
+    /// without allocating, growing the world to cover the section.
+    pub(crate) fn fill_section(&mut self, key: (i32, i32, i32), block: u16) {
+        match block {
+            0 => self.sections.remove(&key),
+            _ => self.sections.insert(key, Section::Uniform(block)),
+        };
+        let corner = BlockPos::new(key.0 * 16, key.1 * 16, key.2 * 16);
+        self.grow_bounds(corner, corner.offset(15, 15, 15));
+    }
+
+    fn grow_bounds(&mut self, low: BlockPos, high: BlockPos) {
+        self.bounds = Some(match self.bounds {
+            Some((min, max)) => (
+                BlockPos::new(min.x.min(low.x), min.y.min(low.y), min.z.min(low.z)),
+                BlockPos::new(max.x.max(high.x), max.y.max(high.y), max.z.max(high.z)),
+            ),
+            None => (low, high),
+        });
+    }
+
+    /// Gets the block at `pos`, which is air anywhere outside of the world.
     pub fn get_block(&self, pos: BlockPos) -> u16 {
-        let idx = (self.sx * self.sy * pos.z) + (self.sx * pos.y) + pos.x;
-        self.data[idx]
+        let (key, idx) = section_index(pos);
+        self.sections
+            .get(&key)
+            .map_or(0, |section| section.get(idx))
+    }
+
+    /// Iterates over every block that isn't air, one section at a time.
+    pub fn blocks(&self) -> impl Iterator<Item = (BlockPos, u16)> + '_ {
+        section_blocks(&self.sections)
+    }
+
+    /// Calls `f` for every block in the box of `size` starting at `min`, with x increasing
+    /// fastest, then z, then y. Sections are looked up once per row of up to 16 blocks rather
+    /// than for every block.
+    pub(crate) fn for_each_block<F>(&self, min: BlockPos, size: (usize, usize, usize), mut f: F)
+    where
+        F: FnMut(BlockPos, u16),
+    {
+        let (sx, sy, sz) = (size.0 as i32, size.1 as i32, size.2 as i32);
+        for y in 0..sy {
+            for z in 0..sz {
+                let mut x = 0;
+                while x < sx {
+                    let pos = min.offset(x, y, z);
+                    let run = (16 - pos.x.rem_euclid(16)).min(sx - x);
+                    let (key, idx) = section_index(pos);
+                    let section = self.sections.get(&key);
+                    for i in 0..run {
+                        let block = section.map_or(0, |section| section.get(idx + i as usize));
+                        f(pos.offset(i, 0, 0), block);
+                    }
+                    x += run;
+                }
+            }
+        }
+    }
+
+    /// The lowest corner of the world's bounds.
+    pub fn min(&self) -> BlockPos {
+        self.bounds.map_or(BlockPos::default(), |(min, _)| min)
+    }
+
+    /// The size of the world's bounds, which is what gets exported.
+    pub fn size(&self) -> (usize, usize, usize) {
+        match self.bounds {
+            Some((min, max)) => (
+                (max.x - min.x + 1) as usize,
+                (max.y - min.y + 1) as usize,
+                (max.z - min.z + 1) as usize,
+            ),
+            None => (0, 0, 0),
+        }
+    }
+
+    /// Moves every block in the world by `x`, `y`, `z`.
+    pub fn translate(&mut self, x: i32, y: i32, z: i32) {
+        let shift = BlockPos::new(x, y, z);
+        let sections = std::mem::take(&mut self.sections);
+        if x % 16 == 0 && y % 16 == 0 && z % 16 == 0 {
+            // Whole sections can be moved as they are
+            self.sections = sections
+                .into_iter()
+                .map(|((sx, sy, sz), section)| ((sx + x / 16, sy + y / 16, sz + z / 16), section))
+                .collect();
+        } else {
+            for (pos, block) in section_blocks(&sections) {
+                self.put_block(pos + shift, block);
+            }
+        }
+        self.bounds = self.bounds.map(|(min, max)| (min + shift, max + shift));
+        self.block_entities = self
+            .block_entities
+            .drain()
+            .map(|(pos, entity)| (pos + shift, entity))
+            .collect();
+    }
+
+    pub fn get_block_state(&self, pos: BlockPos) -> &BlockState {
+        &self.palette[self.get_block(pos) as usize]
+    }
+
+    /// Block states indexed by their palette id.
+    pub fn palette(&self) -> &[BlockState] {
+        &self.palette
+    }
+
+    /// The palette as `Name`/`Properties` compounds, like vanilla structures and litematics use.
+    pub(crate) fn palette_entries(&self) -> Vec<PaletteEntry> {
+        self.palette
+            .iter()
+            .map(|state| PaletteEntry {
+                name: state.name(),
+                properties: state.properties.clone(),
+            })
+            .collect()
     }
 
+    pub fn block_entities(&self) -> impl Iterator<Item = (BlockPos, &BlockEntity)> + '_ {
+        self.block_entities
+            .iter()
+            .map(|(&pos, entity)| (pos, entity))
+    }
+
+    pub fn get_block_entity(&self, pos: BlockPos) -> Option<&BlockEntity> {
+        self.block_entities.get(&pos)
+    }
+
+    /// Attaches `entity` to the block at `pos`, which should be set to a matching block
+    /// separately.
+    pub fn set_block_entity(&mut self, pos: BlockPos, entity: BlockEntity) {
+        self.block_entities.insert(pos, entity);
+    }
+
+    /// Removes the block entity at `pos`, leaving the block itself in place.
+    pub fn remove_block_entity(&mut self, pos: BlockPos) -> Option<BlockEntity> {
+        self.block_entities.remove(&pos)
+    }
+
+    /// Places a barrel that a comparator reads as `ss`.
     pub fn set_barrel(&mut self, pos: BlockPos, ss: u32) {
         let barrel = self.add_block("minecraft:barrel");
         self.set_block(pos, barrel);
-        self.barrels.insert(pos, ss);
+        self.set_block_entity(pos, BlockEntity::barrel(ss));
     }
 
-    pub fn save_schematic(&self, file_name: &str, off_x: i32, off_y: i32, off_z: i32) {
-        let mut file = File::create(file_name).unwrap();
-        let data = self.data(off_x, off_y, off_z);
-        file.write_all(&data).unwrap();
+    /// Copies `other` into this world with its origin at `at`. Blocks are added to this world's
+    /// palette as needed, and block entities are copied along with their blocks.
+    pub fn paste(&mut self, other: &World, at: BlockPos, options: PasteOptions) {
+        let remap = other
+            .palette
+            .iter()
+            .map(|state| self.add_block_state(state))
+            .collect::<Vec<_>>();
+        other.for_each_block(other.min(), other.size(), |src, block| {
+            if block == 0 && options.skip_air {
+                return;
+            }
+            let dst = at + src;
+            if options.only_replace_air && self.get_block(dst) != 0 {
+                return;
+            }
+            self.set_block(dst, remap[block as usize]);
+            match other.block_entities.get(&src) {
+                Some(entity) => self.block_entities.insert(dst, entity.clone()),
+                None => self.block_entities.remove(&dst),
+            };
+        });
     }
 
-    pub fn data(&self, off_x: i32, off_y: i32, off_z: i32) -> Vec<u8> {
-        let mut out = Vec::new();
-
+    /// Loads a Sponge schematic (version 1, 2 or 3) from disk. Blocks are placed relative to the
+    /// position the schematic was copied from, so the stored WorldEdit offset becomes the
+    /// world's lowest corner.
+    pub fn load_schematic(file_name: &str) -> Result<World> {
+        let mut file = BufReader::new(File::open(file_name)?);
         let mut data = Vec::new();
-        for y in 0..self.sy {
-            for z in 0..self.sz {
-                for x in 0..self.sx {
-                    let mut idx = self.get_block(BlockPos::new(x, y, z));
-
-                    loop {
-                        let mut temp = (idx & 0b1111_1111) as u8;
-                        idx >>= 7;
-                        if idx != 0 {
-                            temp |= 0b1000_0000;
-                        }
-                        data.push(temp as i8);
-                        if idx == 0 {
-                            break;
-                        }
-                    }
+        file.read_to_end(&mut data)?;
+        World::from_bytes(&data)
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<World> {
+        let root = nbt::Blob::from_gzip_reader(&mut &data[..])?;
+        // Version 3 nests everything inside of a `Schematic` compound
+        let nested = match root.get("Schematic") {
+            Some(nbt::Value::Compound(schematic)) => Some(schematic),
+            _ => None,
+        };
+        let get = |name| match nested {
+            Some(schematic) => schematic.get(name),
+            None => root.get(name),
+        };
+
+        let version = match get("Version") {
+            Some(&nbt::Value::Int(version)) => version,
+            _ => return Err(Error::MissingTag("Version")),
+        };
+        let (palette, block_data, block_entities) = match version {
+            1 | 2 => (
+                get("Palette"),
+                get("BlockData"),
+                // Version 1 calls these tile entities
+                get("BlockEntities").or_else(|| get("TileEntities")),
+            ),
+            3 => match get("Blocks") {
+                Some(nbt::Value::Compound(blocks)) => (
+                    blocks.get("Palette"),
+                    blocks.get("Data"),
+                    blocks.get("BlockEntities"),
+                ),
+                _ => return Err(Error::MissingTag("Blocks")),
+            },
+            _ => return Err(Error::UnsupportedVersion(version)),
+        };
+
+        let sx = get_short(get("Width"), "Width")? as u16 as usize;
+        let sy = get_short(get("Height"), "Height")? as u16 as usize;
+        let sz = get_short(get("Length"), "Length")? as u16 as usize;
+        let mut world = World::new(sx, sy, sz);
+
+        let palette = match palette {
+            Some(nbt::Value::Compound(palette)) => palette,
+            _ => return Err(Error::MissingTag("Palette")),
+        };
+        let mut entries = palette
+            .iter()
+            .map(|(name, idx)| match idx {
+                &nbt::Value::Int(idx) => Ok((idx as u32, name)),
+                _ => Err(Error::MissingTag("Palette")),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        // Add the states in the order they were saved in rather than the compound's, so that
+        // loading the same file always gives the same palette
+        entries.sort();
+        let mut remap = HashMap::new();
+        for (idx, name) in entries {
+            remap.insert(idx, world.try_add_block(name)?);
+        }
+
+        let block_data = match block_data {
+            Some(nbt::Value::ByteArray(data)) => data,
+            _ => return Err(Error::MissingTag("BlockData")),
+        };
+        let indices = varint::decode(block_data)?;
+        if indices.len() != sx * sy * sz {
+            return Err(Error::InvalidSchematic(format!(
+                "expected {} blocks but found {}",
+                sx * sy * sz,
+                indices.len()
+            )));
+        }
+
+        let mut indices = indices.into_iter();
+        for y in 0..sy {
+            for z in 0..sz {
+                for x in 0..sx {
+                    let idx = indices.next().unwrap();
+                    let block = *remap.get(&idx).ok_or_else(|| {
+                        Error::InvalidSchematic(format!("palette index {} is undefined", idx))
+                    })?;
+                    world.set_block(BlockPos::new(x as i32, y as i32, z as i32), block);
                 }
             }
         }
 
-        let mut encoded_pallete = nbt::Blob::new();
-        for (&entry, &i) in &self.palette {
-            encoded_pallete.insert(entry, i as i32).unwrap();
+        if let Some(nbt::Value::List(block_entities)) = block_entities {
+            for entity in block_entities {
+                let entity = match entity {
+                    nbt::Value::Compound(entity) => entity,
+                    _ => return Err(Error::MissingTag("BlockEntities")),
+                };
+                let id = match entity.get("Id").or_else(|| entity.get("id")) {
+                    Some(nbt::Value::String(id)) => id,
+                    _ => return Err(Error::MissingTag("Id")),
+                };
+                let pos = match entity.get("Pos") {
+                    Some(nbt::Value::IntArray(pos)) if pos.len() == 3 => pos,
+                    _ => return Err(Error::MissingTag("Pos")),
+                };
+                if pos.iter().any(|&c| c < 0)
+                    || pos[0] as usize >= sx
+                    || pos[1] as usize >= sy
+                    || pos[2] as usize >= sz
+                {
+                    return Err(Error::InvalidSchematic(format!(
+                        "block entity at {:?} is out of bounds",
+                        pos
+                    )));
+                }
+                let pos = BlockPos::new(pos[0], pos[1], pos[2]);
+                // Version 3 moves everything but the id and position into `Data`
+                let mut data = match entity.get("Data") {
+                    Some(nbt::Value::Compound(data)) if version == 3 => data.clone(),
+                    _ => entity.clone(),
+                };
+                for key in ["Id", "id", "Pos"] {
+                    data.remove(key);
+                }
+                let entity = BlockEntity {
+                    id: id.clone(),
+                    data,
+                };
+                world.block_entities.insert(pos, entity);
+            }
         }
 
-        let mut block_entities = Vec::new();
-        for (pos, &ss) in &self.barrels {
-            let slots = 27;
-            let items_needed = match ss {
-                0 => 0,
-                15 => slots * 64,
-                _ => ((32 * slots * ss as u32) as f32 / 7.0 - 1.0).ceil() as u32,
-            } as usize;
-            let mut blob = nbt::Blob::new();
-            blob.insert("Id", nbt::Value::String("minecraft:barrel".to_string()))
-                .unwrap();
-            blob.insert(
-                "Pos",
-                nbt::Value::IntArray(vec![pos.x as i32, pos.y as i32, pos.z as i32]),
-            )
-            .unwrap();
-
-            let mut items = Vec::new();
-            for (slot, items_added) in (0..items_needed).step_by(64).enumerate() {
-                let count = (items_needed - items_added).min(64);
-                items.push(nbt::Value::Compound(map! {
-                    "Count" => nbt::Value::Byte(count as i8),
-                    "id" => nbt::Value::String("minecraft:redstone".to_owned()),
-                    "Slot" => nbt::Value::Byte(slot as i8)
-                }));
+        let mut offset = (0, 0, 0);
+        if version == 3 {
+            if let Some(nbt::Value::IntArray(array)) = get("Offset") {
+                if array.len() == 3 {
+                    offset = (array[0], array[1], array[2]);
+                }
             }
-            blob.insert("Items", nbt::Value::List(items)).unwrap();
-            block_entities.push(blob);
+        } else if let Some(nbt::Value::Compound(metadata)) = get("Metadata") {
+            let get_offset = |name| match metadata.get(name) {
+                Some(&nbt::Value::Int(offset)) => offset,
+                _ => 0,
+            };
+            offset = (
+                get_offset("WEOffsetX"),
+                get_offset("WEOffsetY"),
+                get_offset("WEOffsetZ"),
+            );
         }
 
-        let metadata = Metadata {
-            offset_x: off_x as i32,
-            offset_y: off_y as i32,
-            offset_z: off_z as i32,
+        world.translate(offset.0, offset.1, offset.2);
+        Ok(world)
+    }
+
+    /// Saves `area` of the world as a Sponge schematic. The offset is the area's lowest corner,
+    /// so pasting it puts the origin where the player is standing.
+    pub fn save_schematic(
+        &self,
+        file_name: &str,
+        version: SchematicVersion,
+        area: ExportArea,
+    ) -> Result<()> {
+        let data = self.data(version, area)?;
+        let mut file = File::create(file_name)?;
+        file.write_all(&data)?;
+        Ok(())
+    }
+
+    pub fn data(&self, version: SchematicVersion, area: ExportArea) -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+        let region = match area {
+            ExportArea::Bounds => self.bounds.map(|(min, max)| Region::new(min, max)),
+            ExportArea::Crop => self.non_air_region(),
+            ExportArea::Region(region) => Some(region),
         };
-        let schematic = Schematic {
-            width: self.sx as i16,
-            length: self.sz as i16,
-            height: self.sy as i16,
-            block_data: data,
-            block_entities,
-            palette: encoded_pallete,
-            metadata,
-            version: 2,
-            data_version: MC_DATA_VERSION,
+        let (min, (sx, sy, sz)) = region.map_or((BlockPos::default(), (0, 0, 0)), |region| {
+            (region.min, region.size())
+        });
+        // Sizes are unsigned, even though NBT shorts are signed
+        check_dimensions((sx, sy, sz), u16::MAX as usize)?;
+        let (width, height, length) = (sx as u16 as i16, sy as u16 as i16, sz as u16 as i16);
+
+        let mut data = Vec::new();
+        self.for_each_block(min, (sx, sy, sz), |_, block| {
+            varint::encode(block as u32, &mut data)
+        });
+
+        let encoded_pallete = self
+            .palette
+            .iter()
+            .enumerate()
+            .map(|(i, state)| (state.to_string(), i as i32))
+            .collect();
+
+        // Block entities are written in the same order as the block data
+        let mut entities = self
+            .block_entities
+            .iter()
+            .filter(|(&pos, _)| region.is_some_and(|region| region.contains(pos)))
+            .collect::<Vec<_>>();
+        entities.sort_by_key(|(pos, _)| (pos.y, pos.z, pos.x));
+        let mut block_entities = Vec::new();
+        for (&pos, block_entity) in entities {
+            let pos = pos - min;
+            let mut entity = map! {
+                "Id" => nbt::Value::String(block_entity.id.clone()),
+                "Pos" => nbt::Value::IntArray(vec![pos.x, pos.y, pos.z])
+            };
+            match version {
+                SchematicVersion::V2 => entity.extend(block_entity.data.clone()),
+                SchematicVersion::V3 => {
+                    let data = nbt::Value::Compound(block_entity.data.clone());
+                    entity.insert("Data".to_owned(), data);
+                }
+            };
+            block_entities.push(nbt::Value::Compound(entity));
+        }
+
+        match version {
+            SchematicVersion::V2 => {
+                let metadata = Metadata {
+                    offset_x: min.x,
+                    offset_y: min.y,
+                    offset_z: min.z,
+                };
+                let schematic = Schematic {
+                    width,
+                    length,
+                    height,
+                    block_data: data,
+                    block_entities,
+                    palette: encoded_pallete,
+                    metadata,
+                    version: 2,
+                    data_version: MC_DATA_VERSION,
+                };
+                nbt::to_gzip_writer(&mut out, &schematic, Some("Schematic"))?;
+            }
+            SchematicVersion::V3 => {
+                let schematic = SchematicV3 {
+                    width,
+                    length,
+                    height,
+                    offset: vec![min.x, min.y, min.z],
+                    blocks: BlockContainer {
+                        palette: encoded_pallete,
+                        data,
+                        block_entities,
+                    },
+                    version: 3,
+                    data_version: MC_DATA_VERSION,
+                };
+                let root = SchematicV3Root { schematic };
+                nbt::to_gzip_writer(&mut out, &root, None)?;
+            }
+        }
+
+        Ok(out)
+    }
+
+    /// Checks that every dimension fits in a format that can store sizes of up to `max`.
+    pub(crate) fn check_size(&self, max: usize) -> Result<()> {
+        check_dimensions(self.size(), max)
+    }
+}
+
+fn check_dimensions(size: (usize, usize, usize), max: usize) -> Result<()> {
+    let (sx, sy, sz) = size;
+    if sx > max || sy > max || sz > max {
+        return Err(Error::DimensionsTooLarge { size, max });
+    }
+    Ok(())
+}
+
+/// Serializes a list of NBT values with the entries of every compound sorted by name.
+/// `nbt::Value` keeps compounds in a `HashMap`, so they would otherwise be written in a
+/// different order on every run.
+fn sorted_values<S: Serializer>(
+    values: &[nbt::Value],
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    serializer.collect_seq(values.iter().map(Sorted))
+}
+
+struct Sorted<'a>(&'a nbt::Value);
+
+impl Serialize for Sorted<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        match self.0 {
+            nbt::Value::ByteArray(values) => nbt::i8_array(values, serializer),
+            nbt::Value::IntArray(values) => nbt::i32_array(values, serializer),
+            nbt::Value::LongArray(values) => nbt::i64_array(values, serializer),
+            nbt::Value::List(values) => sorted_values(values, serializer),
+            nbt::Value::Compound(values) => {
+                let sorted = values.iter().collect::<BTreeMap<_, _>>();
+                serializer.collect_map(sorted.into_iter().map(|(k, v)| (k, Sorted(v))))
+            }
+            value => value.serialize(serializer),
+        }
+    }
+}
+
+/// Splits a position into the key of its section and its index inside of the section.
+fn section_index(pos: BlockPos) -> ((i32, i32, i32), usize) {
+    let key = (pos.x >> 4, pos.y >> 4, pos.z >> 4);
+    let idx = ((pos.y & 15) << 8) | ((pos.z & 15) << 4) | (pos.x & 15);
+    (key, idx as usize)
+}
+
+/// Iterates over the blocks in `sections` that aren't air, ordered by section.
+fn section_blocks(
+    sections: &HashMap<(i32, i32, i32), Section>,
+) -> impl Iterator<Item = (BlockPos, u16)> + '_ {
+    let mut keys = sections.keys().copied().collect::<Vec<_>>();
+    keys.sort();
+    keys.into_iter().flat_map(move |key| {
+        let section = &sections[&key];
+        let corner = BlockPos::new(key.0 * 16, key.1 * 16, key.2 * 16);
+        let blocks = match section {
+            Section::Uniform(0) => 0..0,
+            _ => 0..4096,
         };
-        nbt::to_gzip_writer(&mut out, &schematic, Some("Schematic")).unwrap();
+        blocks.filter_map(move |i| {
+            let block = section.get(i);
+            let pos = corner.offset((i & 15) as i32, (i >> 8) as i32, ((i >> 4) & 15) as i32);
+            (block != 0).then_some((pos, block))
+        })
+    })
+}
+
+/// 16x16x16 blocks indexed by `y * 256 + z * 16 + x`. Sections filled with a single block
+/// don't allocate anything. A section goes back to being uniform once its last air block is
+/// replaced by the same block as all the others, or once it's all air again, and
+/// `World::fill_section` makes whole sections uniform directly. Other writes don't check
+/// whether a mixed section has become uniform, since that would take a scan of the section.
+#[derive(Clone)]
+enum Section {
+    Uniform(u16),
+    Blocks {
+        blocks: Box<[u16]>,
+        /// How many of the blocks are air
+        air: u16,
+    },
+}
+
+impl Section {
+    fn get(&self, idx: usize) -> u16 {
+        match self {
+            Section::Uniform(block) => *block,
+            Section::Blocks { blocks, .. } => blocks[idx],
+        }
+    }
+
+    fn set(&mut self, idx: usize, block: u16) {
+        match self {
+            Section::Uniform(uniform) if *uniform == block => {}
+            Section::Uniform(uniform) => {
+                let air = match (*uniform, block) {
+                    (0, _) => 4095,
+                    (_, 0) => 1,
+                    _ => 0,
+                };
+                let mut blocks = vec![*uniform; 4096].into_boxed_slice();
+                blocks[idx] = block;
+                *self = Section::Blocks { blocks, air };
+            }
+            Section::Blocks { blocks, air } => {
+                let old = std::mem::replace(&mut blocks[idx], block);
+                match (old, block) {
+                    (0, 0) => {}
+                    (0, _) => *air -= 1,
+                    (_, 0) => *air += 1,
+                    _ => {}
+                }
+                if *air == 4096 {
+                    *self = Section::Uniform(0);
+                } else if *air == 0 && old == 0 && blocks.iter().all(|&b| b == block) {
+                    *self = Section::Uniform(block);
+                }
+            }
+        }
+    }
+}
 
-        out
+fn get_short(value: Option<&nbt::Value>, name: &'static str) -> Result<i16> {
+    match value {
+        Some(&nbt::Value::Short(value)) => Ok(value),
+        _ => Err(Error::MissingTag(name)),
     }
 }
 
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub(crate) struct PaletteEntry {
+    name: String,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    properties: BTreeMap<String, String>,
+}
+
 #[derive(Serialize)]
 struct Metadata {
     #[serde(rename = "WEOffsetX")]
@@ -218,11 +885,42 @@ struct Schematic {
     width: i16,
     length: i16,
     height: i16,
-    palette: nbt::Blob,
+    palette: BTreeMap<String, i32>,
     metadata: Metadata,
     #[serde(serialize_with = "nbt::i8_array")]
     block_data: Vec<i8>,
-    block_entities: Vec<nbt::Blob>,
+    #[serde(serialize_with = "sorted_values")]
+    block_entities: Vec<nbt::Value>,
+    version: i32,
+    data_version: i32,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct BlockContainer {
+    palette: BTreeMap<String, i32>,
+    #[serde(serialize_with = "nbt::i8_array")]
+    data: Vec<i8>,
+    #[serde(serialize_with = "sorted_values")]
+    block_entities: Vec<nbt::Value>,
+}
+
+/// Version 3 schematics are wrapped in an unnamed root compound.
+#[derive(Serialize)]
+struct SchematicV3Root {
+    #[serde(rename = "Schematic")]
+    schematic: SchematicV3,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct SchematicV3 {
+    width: i16,
+    length: i16,
+    height: i16,
+    #[serde(serialize_with = "nbt::i32_array")]
+    offset: Vec<i32>,
+    blocks: BlockContainer,
     version: i32,
     data_version: i32,
 }
@@ -0,0 +1,259 @@
+use redstone_schem::block_entity::BlockEntity;
+use redstone_schem::error::Error;
+use redstone_schem::world::{BlockPos, World, MC_DATA_VERSION};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// An empty directory for a save that only this test uses
+fn save_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "redstone_schem_anvil_{}_{}",
+        name,
+        std::process::id()
+    ));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+fn region_path(dir: &Path, cx: i32, cz: i32) -> PathBuf {
+    dir.join("region")
+        .join(format!("r.{}.{}.mca", cx >> 5, cz >> 5))
+}
+
+/// Reads a chunk straight out of its region file
+fn read_chunk(dir: &Path, cx: i32, cz: i32) -> Option<nbt::Blob> {
+    let data = fs::read(region_path(dir, cx, cz)).unwrap();
+    assert_eq!(
+        data.len() % 4096,
+        0,
+        "region files are padded to whole sectors"
+    );
+    let idx = ((cx & 31) + (cz & 31) * 32) as usize;
+    let location = u32::from_be_bytes(data[idx * 4..idx * 4 + 4].try_into().unwrap());
+    if location == 0 {
+        return None;
+    }
+    let (offset, sectors) = ((location >> 8) as usize * 4096, (location & 255) as usize);
+    let len = u32::from_be_bytes(data[offset..offset + 4].try_into().unwrap()) as usize;
+    assert!(len + 4 <= sectors * 4096);
+    assert_eq!(data[offset + 4], 2, "chunks are zlib compressed");
+    Some(nbt::Blob::from_zlib_reader(&mut &data[offset + 5..offset + 4 + len]).unwrap())
+}
+
+fn level(chunk: &nbt::Blob) -> &HashMap<String, nbt::Value> {
+    match chunk.get("Level") {
+        Some(nbt::Value::Compound(level)) => level,
+        _ => panic!("chunk without a level"),
+    }
+}
+
+fn compound(value: &nbt::Value) -> &HashMap<String, nbt::Value> {
+    match value {
+        nbt::Value::Compound(compound) => compound,
+        _ => panic!("expected a compound, got {:?}", value),
+    }
+}
+
+/// The block at a position within the chunk, as `name[key=value,...]`
+fn block_at(chunk: &nbt::Blob, x: i32, y: i32, z: i32) -> String {
+    let sections = match level(chunk).get("Sections") {
+        Some(nbt::Value::List(sections)) => sections,
+        _ => panic!("chunk without sections"),
+    };
+    let section = sections
+        .iter()
+        .map(compound)
+        .find(|section| section.get("Y") == Some(&nbt::Value::Byte((y >> 4) as i8)));
+    let section = match section {
+        Some(section) => section,
+        None => return "minecraft:air".to_owned(),
+    };
+    let palette = match section.get("Palette") {
+        Some(nbt::Value::List(palette)) => palette,
+        _ => panic!("section without a palette"),
+    };
+    let states = match section.get("BlockStates") {
+        Some(nbt::Value::LongArray(states)) => states,
+        _ => panic!("section without block states"),
+    };
+
+    let bits = (usize::BITS - (palette.len() - 1).leading_zeros()).max(4) as usize;
+    let per_long = 64 / bits;
+    let i = ((y & 15) * 256 + (z & 15) * 16 + (x & 15)) as usize;
+    let idx = (states[i / per_long] as u64 >> (i % per_long * bits)) & ((1 << bits) - 1);
+    let entry = compound(&palette[idx as usize]);
+    let mut name = match entry.get("Name") {
+        Some(nbt::Value::String(name)) => name.clone(),
+        _ => panic!("palette entry without a name"),
+    };
+    if let Some(nbt::Value::Compound(properties)) = entry.get("Properties") {
+        let mut properties = properties
+            .iter()
+            .map(|(k, v)| match v {
+                nbt::Value::String(v) => format!("{}={}", k, v),
+                _ => panic!("property that isn't a string"),
+            })
+            .collect::<Vec<_>>();
+        properties.sort();
+        name = format!("{}[{}]", name, properties.join(","));
+    }
+    name
+}
+
+/// The block entities in the chunk by their position
+fn block_entities(chunk: &nbt::Blob) -> HashMap<(i32, i32, i32), HashMap<String, nbt::Value>> {
+    let entities = match level(chunk).get("TileEntities") {
+        Some(nbt::Value::List(entities)) => entities,
+        _ => panic!("chunk without block entities"),
+    };
+    entities
+        .iter()
+        .map(|entity| {
+            let entity = compound(entity);
+            let coord = |name| match entity.get(name) {
+                Some(&nbt::Value::Int(coord)) => coord,
+                _ => panic!("block entity without {}", name),
+            };
+            ((coord("x"), coord("y"), coord("z")), entity.clone())
+        })
+        .collect()
+}
+
+#[test]
+fn saves_blocks_and_block_entities() {
+    let dir = save_dir("save");
+    let mut world = World::new(20, 3, 20);
+    let stone = world.add_block("minecraft:stone");
+    let repeater = world.add_block("minecraft:repeater[delay=2,facing=south]");
+    for x in 0..20 {
+        world.set_block(BlockPos::new(x, 0, x), stone);
+    }
+    world.set_block(BlockPos::new(1, 1, 1), repeater);
+    world.set_barrel(BlockPos::new(5, 2, 5), 4);
+    world
+        .save_to_region(dir.to_str().unwrap(), -10, 64, -3)
+        .unwrap();
+
+    // The world spans chunks -1 to 0 on x and -1 to 1 on z, all in regions -1 and 0
+    for (cx, cz) in [(-1, -1), (-1, 0), (0, 0), (0, 1)] {
+        let chunk = read_chunk(&dir, cx, cz).unwrap();
+        assert_eq!(
+            chunk.get("DataVersion"),
+            Some(&nbt::Value::Int(MC_DATA_VERSION))
+        );
+        let level = level(&chunk);
+        assert_eq!(level.get("xPos"), Some(&nbt::Value::Int(cx)));
+        assert_eq!(level.get("zPos"), Some(&nbt::Value::Int(cz)));
+    }
+
+    let chunk = read_chunk(&dir, -1, -1).unwrap();
+    assert_eq!(block_at(&chunk, -10, 64, -3), "minecraft:stone");
+    assert_eq!(block_at(&chunk, -9, 64, -3), "minecraft:air");
+    assert_eq!(
+        block_at(&chunk, -9, 65, -2),
+        "minecraft:repeater[delay=2,facing=south]"
+    );
+    let chunk = read_chunk(&dir, 0, 0).unwrap();
+    assert_eq!(block_at(&chunk, 0, 64, 7), "minecraft:stone");
+    assert_eq!(block_at(&chunk, 1, 64, 7), "minecraft:air");
+    assert_eq!(block_at(&chunk, 0, 63, 7), "minecraft:air");
+    let chunk = read_chunk(&dir, 0, 1).unwrap();
+    assert_eq!(block_at(&chunk, 9, 64, 16), "minecraft:stone");
+
+    let chunk = read_chunk(&dir, -1, 0).unwrap();
+    assert_eq!(block_at(&chunk, -5, 66, 2), "minecraft:barrel");
+    let entities = block_entities(&chunk);
+    assert_eq!(entities.len(), 1);
+    let barrel = &entities[&(-5, 66, 2)];
+    let mut expected = BlockEntity::barrel(4).data;
+    expected.insert(
+        "id".to_owned(),
+        nbt::Value::String("minecraft:barrel".to_owned()),
+    );
+    for (k, v) in &expected {
+        assert_eq!(barrel.get(k), Some(v), "{}", k);
+    }
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn updates_existing_chunks() {
+    let dir = save_dir("update");
+    let path = dir.to_str().unwrap();
+    let mut world = World::new(8, 2, 8);
+    let stone = world.add_block("minecraft:stone");
+    world.set_block(BlockPos::new(0, 0, 0), stone);
+    world.set_block(BlockPos::new(7, 1, 7), stone);
+    world.set_barrel(BlockPos::new(3, 0, 3), 1);
+    world.save_to_region(path, 0, 10, 0).unwrap();
+
+    // A smaller world only replaces what's inside of its own bounds
+    let mut patch = World::new(4, 1, 4);
+    let glass = patch.add_block("minecraft:glass");
+    patch.set_block(BlockPos::new(0, 0, 0), glass);
+    patch.save_to_region(path, 0, 10, 0).unwrap();
+
+    let chunk = read_chunk(&dir, 0, 0).unwrap();
+    assert_eq!(block_at(&chunk, 0, 10, 0), "minecraft:glass");
+    assert_eq!(block_at(&chunk, 3, 10, 3), "minecraft:air");
+    assert_eq!(block_at(&chunk, 7, 11, 7), "minecraft:stone");
+    assert!(block_entities(&chunk).is_empty());
+    // Only one chunk was ever written
+    assert_eq!(
+        fs::metadata(region_path(&dir, 0, 0)).unwrap().len(),
+        3 * 4096
+    );
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn rejects_chunks_from_older_versions() {
+    let dir = save_dir("old");
+    fs::create_dir_all(dir.join("region")).unwrap();
+    // A 1.16 chunk at 0, 0
+    let mut chunk = nbt::Blob::new();
+    chunk.insert("DataVersion", 2586).unwrap();
+    let level = HashMap::from([
+        ("xPos".to_owned(), nbt::Value::Int(0)),
+        ("zPos".to_owned(), nbt::Value::Int(0)),
+    ]);
+    chunk.insert("Level", nbt::Value::Compound(level)).unwrap();
+    let mut compressed = Vec::new();
+    chunk.to_zlib_writer(&mut compressed).unwrap();
+    let mut region = vec![0u8; 2 * 4096];
+    region[..4].copy_from_slice(&(2u32 << 8 | 1).to_be_bytes());
+    region.extend_from_slice(&(compressed.len() as u32 + 1).to_be_bytes());
+    region.push(2);
+    region.extend_from_slice(&compressed);
+    region.resize(3 * 4096, 0);
+    fs::write(region_path(&dir, 0, 0), &region).unwrap();
+
+    let mut world = World::new(1, 1, 1);
+    let stone = world.add_block("minecraft:stone");
+    world.set_block(BlockPos::new(0, 0, 0), stone);
+    let result = world.save_to_region(dir.to_str().unwrap(), 0, 64, 0);
+    assert!(matches!(result, Err(Error::InvalidChunk(_))));
+    assert_eq!(fs::read(region_path(&dir, 0, 0)).unwrap(), region);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn rejects_worlds_outside_of_the_build_height() {
+    let dir = save_dir("height");
+    let path = dir.to_str().unwrap();
+    let mut world = World::new(1, 2, 1);
+    let stone = world.add_block("minecraft:stone");
+    world.set_block(BlockPos::new(0, 0, 0), stone);
+    assert!(matches!(
+        world.save_to_region(path, 0, -1, 0),
+        Err(Error::OutsideBuildHeight { y: -1 })
+    ));
+    assert!(matches!(
+        world.save_to_region(path, 0, 255, 0),
+        Err(Error::OutsideBuildHeight { y: 256 })
+    ));
+    assert!(!dir.exists());
+    world.save_to_region(path, 0, 254, 0).unwrap();
+    let _ = fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,133 @@
+use redstone_schem::basic::{create_path, create_wire, WireOptions};
+use redstone_schem::error::Error;
+use redstone_schem::world::{BlockPos, World};
+
+#[test]
+fn repeaters_skip_corners() {
+    let mut world = World::default();
+    let stone = world.add_block("minecraft:stone");
+    let waypoints = [
+        BlockPos::new(0, 0, 0),
+        BlockPos::new(15, 0, 0),
+        BlockPos::new(15, 0, 20),
+    ];
+    let routed = create_path(&mut world, stone, &waypoints, WireOptions::default()).unwrap();
+    let top = |x, z| world.get_block_state(BlockPos::new(x, 1, z)).to_string();
+    assert_eq!(top(14, 0), "minecraft:repeater[facing=west]");
+    assert_eq!(top(15, 0), "minecraft:redstone_wire");
+    assert_eq!(top(15, 15), "minecraft:repeater[facing=north]");
+    assert_eq!((routed.delay, routed.ss), (2, 11));
+    assert_eq!(routed.output, BlockPos::new(15, 1, 20));
+}
+
+#[test]
+fn long_runs_of_corners_lose_the_signal() {
+    let mut world = World::default();
+    let stone = world.add_block("minecraft:stone");
+    // A diagonal staircase of wire where every block is a corner
+    let waypoints = (0..20)
+        .map(|i| BlockPos::new((i + 1) / 2, 0, i / 2))
+        .collect::<Vec<_>>();
+    let result = create_path(&mut world, stone, &waypoints, WireOptions::default());
+    assert!(matches!(
+        result,
+        Err(Error::SignalLost { at }) if at == waypoints[15]
+    ));
+    assert_eq!(world.blocks().filter(|&(_, block)| block != 0).count(), 0);
+
+    // Short enough runs still make it
+    let routed = create_path(&mut world, stone, &waypoints[..15], WireOptions::default()).unwrap();
+    assert_eq!((routed.delay, routed.ss), (0, 1));
+}
+
+#[test]
+fn straight_wires() {
+    let mut world = World::default();
+    let stone = world.add_block("minecraft:stone");
+    let start = BlockPos::new(0, 0, 0);
+    let routed = create_wire(
+        &mut world,
+        stone,
+        start,
+        start.offset(40, 0, 0),
+        WireOptions::default(),
+    )
+    .unwrap();
+    assert_eq!((routed.delay, routed.ss), (2, 7));
+    assert_eq!(routed.footprint.size(), (41, 2, 1));
+}
+
+#[test]
+fn input_and_min_output() {
+    let start = BlockPos::new(0, 0, 0);
+    let end = start.offset(10, 0, 0);
+    // (input, min_output, repeated) -> (delay, output)
+    for (input, min_output, repeated, expected) in [
+        (15, 1, true, (0, 5)),
+        (5, 1, true, (1, 11)),
+        (11, 1, true, (0, 1)),
+        (10, 1, true, (1, 15)),
+        (15, 8, true, (1, 15)),
+        (15, 5, true, (0, 5)),
+        (15, 0, false, (0, 5)),
+        (3, 0, false, (0, 0)),
+    ] {
+        let mut world = World::default();
+        let stone = world.add_block("minecraft:stone");
+        let options = WireOptions {
+            input,
+            min_output,
+            repeated,
+        };
+        let routed = create_wire(&mut world, stone, start, end, options).unwrap();
+        assert_eq!((routed.delay, routed.ss), expected, "{:?}", options);
+    }
+}
+
+#[test]
+fn unreachable_outputs_are_errors() {
+    let mut world = World::default();
+    let stone = world.add_block("minecraft:stone");
+    let start = BlockPos::new(0, 0, 0);
+    let wire = |world: &mut World, length, options| {
+        create_wire(world, stone, start, start.offset(length, 0, 0), options)
+    };
+
+    let unrepeated = WireOptions {
+        repeated: false,
+        ..Default::default()
+    };
+    assert!(matches!(
+        wire(&mut world, 20, unrepeated),
+        Err(Error::SignalTooWeak { ss: 0, min: 1 })
+    ));
+    let weak = WireOptions {
+        input: 4,
+        min_output: 6,
+        ..unrepeated
+    };
+    assert!(matches!(
+        wire(&mut world, 0, weak),
+        Err(Error::SignalTooWeak { ss: 4, min: 6 })
+    ));
+    for options in [
+        WireOptions {
+            input: 0,
+            ..Default::default()
+        },
+        WireOptions {
+            input: 16,
+            ..Default::default()
+        },
+        WireOptions {
+            min_output: 16,
+            ..Default::default()
+        },
+    ] {
+        assert!(matches!(
+            wire(&mut world, 5, options),
+            Err(Error::InvalidSignalStrength(_))
+        ));
+    }
+    assert_eq!(world.blocks().filter(|&(_, block)| block != 0).count(), 0);
+}
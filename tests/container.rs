@@ -0,0 +1,178 @@
+use redstone_schem::block_entity::ItemStack;
+use redstone_schem::container::{fill_items, lectern_output, signal_strength, Container};
+
+const INVENTORIES: [Container; 13] = [
+    Container::Barrel,
+    Container::Chest,
+    Container::TrappedChest,
+    Container::DoubleChest,
+    Container::DoubleTrappedChest,
+    Container::ShulkerBox,
+    Container::Hopper,
+    Container::Dropper,
+    Container::Dispenser,
+    Container::Furnace,
+    Container::BlastFurnace,
+    Container::Smoker,
+    Container::BrewingStand,
+];
+
+/// The comparator formula from the game, written out with exact integer math rather than floats
+fn vanilla_output(slots: usize, stacks: &[(u8, u8)]) -> u32 {
+    // Every stack size divides 64, so fullness is a whole number of 64ths of a slot
+    let fullness = stacks
+        .iter()
+        .map(|&(count, max_stack)| count as usize * (64 / max_stack as usize))
+        .sum::<usize>();
+    match stacks.iter().any(|&(count, _)| count > 0) {
+        true => (fullness * 14 / (64 * slots)) as u32 + 1,
+        false => 0,
+    }
+}
+
+fn counts(items: &[ItemStack], max_stack: u8) -> Vec<(u8, u8)> {
+    items.iter().map(|item| (item.count, max_stack)).collect()
+}
+
+#[test]
+fn signal_strength_matches_vanilla() {
+    for slots in [3, 5, 9, 27, 54] {
+        for max_stack in [1, 16, 64] {
+            for count in 0..=slots * max_stack as usize {
+                let stacks = (0..count)
+                    .step_by(max_stack as usize)
+                    .map(|added| ((count - added).min(max_stack as usize) as u8, max_stack))
+                    .collect::<Vec<_>>();
+                assert_eq!(
+                    signal_strength(slots, &stacks),
+                    vanilla_output(slots, &stacks),
+                    "{} items stacking to {} in {} slots",
+                    count,
+                    max_stack,
+                    slots
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn fills_are_exact_and_minimal() {
+    for container in INVENTORIES {
+        let slots = container.slots().unwrap();
+        for max_stack in [1, 16, 64] {
+            for ss in 0..=15 {
+                let total = |items: &[ItemStack]| items.iter().map(|i| i.count as usize).sum();
+                // The fewest items that reach `ss`, found by trying every amount
+                let reachable = (0..=slots * max_stack as usize).find(|&count| {
+                    let stacks = (0..count)
+                        .step_by(max_stack as usize)
+                        .map(|added| ((count - added).min(max_stack as usize) as u8, max_stack))
+                        .collect::<Vec<_>>();
+                    vanilla_output(slots, &stacks) == ss
+                });
+
+                let items = fill_items(slots, ss, "minecraft:stone", max_stack);
+                assert_eq!(items.as_deref().map(total), reachable);
+                let items = match items {
+                    Some(items) => items,
+                    None => continue,
+                };
+                assert!(items.len() <= slots);
+                assert!(items.iter().all(|item| item.count <= max_stack));
+                assert_eq!(vanilla_output(slots, &counts(&items, max_stack)), ss);
+
+                let filled = container.fill(ss, "minecraft:stone", max_stack).unwrap();
+                assert_eq!(filled.state.name(), container.id());
+                let entity = filled.entity.unwrap();
+                assert_eq!(entity.id, container.id());
+                assert_eq!(filled.other_half.is_some(), slots == 54);
+                if let Some((state, other)) = filled.other_half {
+                    assert_eq!(state.name(), container.id());
+                    assert_eq!(filled.state.property("type"), Some("right"));
+                    assert_eq!(state.property("type"), Some("left"));
+                    // Together the halves hold exactly the items that were asked for
+                    let halves = [&entity, &other].map(|half| match half.data.get("Items") {
+                        Some(nbt::Value::List(items)) => items.len(),
+                        _ => panic!("chest without items"),
+                    });
+                    assert!(halves.iter().all(|&len| len <= 27));
+                    assert_eq!(halves[0] + halves[1], items.len());
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn unreachable_signals() {
+    // Five unstackable items can only make multiples of three
+    assert!(fill_items(5, 2, "minecraft:diamond_sword", 1).is_none());
+    assert!(fill_items(5, 3, "minecraft:diamond_sword", 1).is_some());
+    assert!(fill_items(27, 16, "minecraft:redstone", 64).is_none());
+    assert!(Container::Cauldron.fill(4, "", 64).is_none());
+    assert!(Container::Composter.fill(9, "", 64).is_none());
+    // There are only 13 music discs in 1.17
+    assert!(Container::Jukebox.fill(14, "", 64).is_none());
+}
+
+#[test]
+fn stack_sizes_must_be_real() {
+    for max_stack in [0, 65, 255] {
+        assert!(fill_items(27, 1, "minecraft:stone", max_stack).is_none());
+        assert!(Container::Barrel
+            .fill(1, "minecraft:stone", max_stack)
+            .is_none());
+        assert!(Container::DoubleChest
+            .fill(1, "minecraft:stone", max_stack)
+            .is_none());
+    }
+    assert!(fill_items(27, 15, "minecraft:stone", 64).is_some());
+}
+
+#[test]
+fn other_blocks() {
+    for ss in 0..=13 {
+        let filled = Container::Jukebox.fill(ss, "", 64).unwrap();
+        assert_eq!(filled.entity.is_some(), ss > 0);
+    }
+    for ss in 0..=15 {
+        let filled = Container::Lectern.fill(ss, "", 64).unwrap();
+        let entity = match filled.entity {
+            Some(entity) => entity,
+            None => {
+                assert_eq!(ss, 0);
+                continue;
+            }
+        };
+        let page = match entity.data.get("Page") {
+            Some(&nbt::Value::Int(page)) => page as u32,
+            _ => panic!("lectern without a page"),
+        };
+        let pages = match entity.data.get("Book") {
+            Some(nbt::Value::Compound(book)) => match book.get("tag") {
+                Some(nbt::Value::Compound(tag)) => match tag.get("pages") {
+                    Some(nbt::Value::List(pages)) => pages.len() as u32,
+                    _ => 0,
+                },
+                _ => 0,
+            },
+            _ => 0,
+        };
+        assert_eq!(lectern_output(pages, page), ss);
+    }
+    for ss in 0..=3 {
+        let filled = Container::Cauldron.fill(ss, "", 64).unwrap();
+        assert_eq!(
+            filled.state.property("level"),
+            (ss > 0).then(|| ss.to_string()).as_deref()
+        );
+    }
+    for ss in 0..=8 {
+        let filled = Container::Composter.fill(ss, "", 64).unwrap();
+        assert_eq!(
+            filled.state.property("level"),
+            Some(ss.to_string().as_str())
+        );
+    }
+}
@@ -0,0 +1,202 @@
+//! Locks the exact output of a small reference world. Set `UPDATE_GOLDEN=1` to rewrite the
+//! files in `tests/golden` after an intentional format change.
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use redstone_schem::region::Region;
+use redstone_schem::world::{BlockPos, ExportArea, SchematicVersion, World};
+use std::fs;
+use std::io::{Read, Write};
+use std::path::Path;
+
+fn reference_world() -> World {
+    let mut world = World::new(3, 2, 2);
+    let stone = world.add_block("minecraft:smooth_stone");
+    let wire = world
+        .add_block("minecraft:redstone_wire[east=side,north=none,power=0,south=none,west=side]");
+    let repeater = world.add_block("minecraft:repeater[delay=2,facing=west]");
+    for x in 0..3 {
+        world.set_block(BlockPos::new(x, 0, 0), stone);
+    }
+    world.set_block(BlockPos::new(0, 1, 0), wire);
+    world.set_block(BlockPos::new(1, 1, 0), repeater);
+    world.set_block(BlockPos::new(2, 1, 0), wire);
+    world.set_barrel(BlockPos::new(0, 0, 1), 3);
+    world.set_barrel(BlockPos::new(2, 1, 1), 15);
+    world.translate(1, -2, 3);
+    world
+}
+
+fn golden_path(name: &str) -> std::path::PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(name)
+}
+
+/// Compares the uncompressed NBT, so the test doesn't depend on the gzip implementation.
+fn check_golden(name: &str, data: &[u8]) {
+    let mut nbt = Vec::new();
+    GzDecoder::new(data).read_to_end(&mut nbt).unwrap();
+
+    let path = golden_path(name);
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::write(&path, &nbt).unwrap();
+    }
+    let expected = fs::read(&path).unwrap();
+    assert!(
+        nbt == expected,
+        "{} does not match {}",
+        name,
+        path.display()
+    );
+}
+
+#[test]
+fn schematic_v2() {
+    let data = reference_world()
+        .data(SchematicVersion::V2, ExportArea::Bounds)
+        .unwrap();
+    check_golden("reference_v2.nbt", &data);
+}
+
+#[test]
+fn schematic_v3() {
+    let data = reference_world()
+        .data(SchematicVersion::V3, ExportArea::Bounds)
+        .unwrap();
+    check_golden("reference_v3.nbt", &data);
+}
+
+#[test]
+fn repeated_saves_are_identical() {
+    for version in [SchematicVersion::V2, SchematicVersion::V3] {
+        let first = reference_world().data(version, ExportArea::Bounds).unwrap();
+        for _ in 0..8 {
+            assert!(reference_world().data(version, ExportArea::Bounds).unwrap() == first);
+        }
+    }
+}
+
+#[test]
+fn loading_and_saving_round_trips() {
+    for (version, name) in [
+        (SchematicVersion::V2, "reference_v2.nbt"),
+        (SchematicVersion::V3, "reference_v3.nbt"),
+    ] {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder
+            .write_all(&fs::read(golden_path(name)).unwrap())
+            .unwrap();
+        let file = encoder.finish().unwrap();
+
+        let world = World::from_bytes(&file).unwrap();
+        let again = World::from_bytes(&file).unwrap();
+        assert_eq!(world.palette(), again.palette());
+        check_golden(name, &world.data(version, ExportArea::Bounds).unwrap());
+    }
+}
+
+/// The size, offset and block entity positions stored in an exported schematic
+#[derive(Debug, PartialEq)]
+struct Layout {
+    size: (i16, i16, i16),
+    offset: [i32; 3],
+    block_entities: Vec<[i32; 3]>,
+}
+
+fn exported_layout(data: &[u8]) -> Layout {
+    let root = nbt::Blob::from_gzip_reader(&mut &data[..]).unwrap();
+    let v3 = match root.get("Schematic") {
+        Some(nbt::Value::Compound(schematic)) => Some(schematic.clone()),
+        _ => None,
+    };
+    let get = |name: &'static str| match &v3 {
+        Some(schematic) => schematic.get(name).cloned(),
+        None => root.get(name).cloned(),
+    };
+    let short = |name| match get(name) {
+        Some(nbt::Value::Short(v)) => v,
+        other => panic!("{} is {:?}", name, other),
+    };
+    let size = (short("Width"), short("Height"), short("Length"));
+
+    let (offset, entities) = match (&v3, get("Offset"), get("Metadata"), get("Blocks")) {
+        (Some(_), Some(nbt::Value::IntArray(offset)), _, Some(nbt::Value::Compound(blocks))) => {
+            (offset, blocks.get("BlockEntities").cloned())
+        }
+        (None, _, Some(nbt::Value::Compound(metadata)), _) => {
+            let offset = ["WEOffsetX", "WEOffsetY", "WEOffsetZ"]
+                .iter()
+                .map(|name| match metadata.get(*name) {
+                    Some(&nbt::Value::Int(v)) => v,
+                    other => panic!("{} is {:?}", name, other),
+                })
+                .collect();
+            (offset, get("BlockEntities"))
+        }
+        _ => panic!("the schematic has no offset"),
+    };
+    let positions = match entities {
+        Some(nbt::Value::List(entities)) => entities
+            .iter()
+            .map(|entity| match entity {
+                nbt::Value::Compound(entity) => match entity.get("Pos") {
+                    Some(nbt::Value::IntArray(pos)) => [pos[0], pos[1], pos[2]],
+                    other => panic!("Pos is {:?}", other),
+                },
+                other => panic!("block entity is {:?}", other),
+            })
+            .collect(),
+        other => panic!("BlockEntities is {:?}", other),
+    };
+    Layout {
+        size,
+        offset: [offset[0], offset[1], offset[2]],
+        block_entities: positions,
+    }
+}
+
+#[test]
+fn export_areas() {
+    let mut world = reference_world();
+    // Setting air far away grows the bounds without adding anything to crop
+    world.set_block(BlockPos::new(20, 10, -5), 0);
+    assert_eq!(world.size(), (20, 13, 10));
+
+    // One of the barrels and empty space past the edge of the world
+    let region = Region::new(BlockPos::new(2, -2, 4), BlockPos::new(25, -1, 4));
+    for version in [SchematicVersion::V2, SchematicVersion::V3] {
+        let crop = world.data(version, ExportArea::Crop).unwrap();
+        assert_eq!(
+            exported_layout(&crop),
+            Layout {
+                size: (3, 2, 2),
+                offset: [1, -2, 3],
+                block_entities: vec![[0, 0, 1], [2, 1, 1]],
+            }
+        );
+        let cropped = World::from_bytes(&crop).unwrap();
+        assert_eq!(cropped.min(), BlockPos::new(1, -2, 3));
+        assert_eq!(cropped.size(), (3, 2, 2));
+
+        let part = world.data(version, ExportArea::Region(region)).unwrap();
+        assert_eq!(
+            exported_layout(&part),
+            Layout {
+                size: (24, 2, 1),
+                offset: [2, -2, 4],
+                block_entities: vec![[1, 1, 0]],
+            }
+        );
+        let part = World::from_bytes(&part).unwrap();
+        assert_eq!(part.min(), BlockPos::new(2, -2, 4));
+        assert_eq!(part.size(), (24, 2, 1));
+        assert_eq!(
+            part.get_block_state(BlockPos::new(3, -1, 4)).to_string(),
+            "minecraft:barrel"
+        );
+        assert!(part.get_block_entity(BlockPos::new(3, -1, 4)).is_some());
+        assert_eq!(part.block_entities().count(), 1);
+    }
+}
@@ -0,0 +1,314 @@
+//! Loads schematics built tag by tag the way each version of the Sponge format lays them out.
+
+use redstone_schem::error::Error;
+use redstone_schem::varint;
+use redstone_schem::world::{BlockPos, World};
+use std::collections::HashMap;
+
+const SIZE: (i16, i16, i16) = (3, 2, 2);
+const CHEST: &str = "minecraft:chest[facing=west,type=single]";
+const SIGN: &str = "minecraft:oak_sign[rotation=4,waterlogged=false]";
+
+fn compound(entries: Vec<(&str, nbt::Value)>) -> nbt::Value {
+    nbt::Value::Compound(
+        entries
+            .into_iter()
+            .map(|(k, v)| (k.to_owned(), v))
+            .collect(),
+    )
+}
+
+fn string(value: &str) -> nbt::Value {
+    nbt::Value::String(value.to_owned())
+}
+
+fn palette() -> nbt::Value {
+    compound(vec![
+        ("minecraft:air", nbt::Value::Int(0)),
+        ("minecraft:stone", nbt::Value::Int(1)),
+        (CHEST, nbt::Value::Int(2)),
+        (SIGN, nbt::Value::Int(3)),
+    ])
+}
+
+/// Stone along the bottom, a chest above the first corner and a sign above the opposite one
+fn block_data() -> nbt::Value {
+    let (w, h, l) = (SIZE.0 as usize, SIZE.1 as usize, SIZE.2 as usize);
+    let mut data = Vec::new();
+    for y in 0..h {
+        for z in 0..l {
+            for x in 0..w {
+                let block = match (x, y, z) {
+                    (_, 0, _) => 1,
+                    (0, 1, 0) => 2,
+                    (2, 1, 1) => 3,
+                    _ => 0,
+                };
+                varint::encode(block, &mut data);
+            }
+        }
+    }
+    nbt::Value::ByteArray(data)
+}
+
+/// The data of the chest and the sign, without their id and position
+fn entity_data() -> [(&'static str, [i32; 3], HashMap<String, nbt::Value>); 2] {
+    let item = compound(vec![
+        ("Count", nbt::Value::Byte(3)),
+        ("id", string("minecraft:redstone")),
+        ("Slot", nbt::Value::Byte(0)),
+    ]);
+    let chest = compound(vec![("Items", nbt::Value::List(vec![item]))]);
+    let sign = compound(vec![
+        ("Text1", string("{\"text\":\"hello\"}")),
+        ("Color", string("black")),
+    ]);
+    let unwrap = |value| match value {
+        nbt::Value::Compound(data) => data,
+        _ => unreachable!(),
+    };
+    [
+        ("minecraft:chest", [0, 1, 0], unwrap(chest)),
+        ("minecraft:sign", [2, 1, 1], unwrap(sign)),
+    ]
+}
+
+/// Block entities with their data next to the id and position, as in versions 1 and 2
+fn flat_block_entities() -> nbt::Value {
+    let entities = entity_data()
+        .into_iter()
+        .map(|(id, pos, mut data)| {
+            data.insert("Id".to_owned(), string(id));
+            data.insert("Pos".to_owned(), nbt::Value::IntArray(pos.to_vec()));
+            nbt::Value::Compound(data)
+        })
+        .collect();
+    nbt::Value::List(entities)
+}
+
+fn dimensions() -> Vec<(&'static str, nbt::Value)> {
+    vec![
+        ("Width", nbt::Value::Short(SIZE.0)),
+        ("Height", nbt::Value::Short(SIZE.1)),
+        ("Length", nbt::Value::Short(SIZE.2)),
+    ]
+}
+
+fn offset_metadata(offset: BlockPos) -> nbt::Value {
+    compound(vec![
+        ("WEOffsetX", nbt::Value::Int(offset.x)),
+        ("WEOffsetY", nbt::Value::Int(offset.y)),
+        ("WEOffsetZ", nbt::Value::Int(offset.z)),
+    ])
+}
+
+fn v1(offset: BlockPos) -> Vec<(&'static str, nbt::Value)> {
+    let mut tags = dimensions();
+    tags.extend([
+        ("Version", nbt::Value::Int(1)),
+        ("Palette", palette()),
+        ("PaletteMax", nbt::Value::Int(4)),
+        ("BlockData", block_data()),
+        ("TileEntities", flat_block_entities()),
+        ("Metadata", offset_metadata(offset)),
+    ]);
+    tags
+}
+
+fn v2(offset: BlockPos) -> Vec<(&'static str, nbt::Value)> {
+    let mut tags = dimensions();
+    tags.extend([
+        ("Version", nbt::Value::Int(2)),
+        ("DataVersion", nbt::Value::Int(2730)),
+        ("Palette", palette()),
+        ("PaletteMax", nbt::Value::Int(4)),
+        ("BlockData", block_data()),
+        ("BlockEntities", flat_block_entities()),
+        ("Metadata", offset_metadata(offset)),
+    ]);
+    tags
+}
+
+/// Version 3 nests everything in `Schematic` and the block entity data in `Data`
+fn v3(offset: BlockPos) -> Vec<(&'static str, nbt::Value)> {
+    let entities = entity_data()
+        .into_iter()
+        .map(|(id, pos, data)| {
+            compound(vec![
+                ("Id", string(id)),
+                ("Pos", nbt::Value::IntArray(pos.to_vec())),
+                ("Data", nbt::Value::Compound(data)),
+            ])
+        })
+        .collect();
+    let blocks = compound(vec![
+        ("Palette", palette()),
+        ("Data", block_data()),
+        ("BlockEntities", nbt::Value::List(entities)),
+    ]);
+    let mut tags = dimensions();
+    tags.extend([
+        ("Version", nbt::Value::Int(3)),
+        ("DataVersion", nbt::Value::Int(2730)),
+        (
+            "Offset",
+            nbt::Value::IntArray(vec![offset.x, offset.y, offset.z]),
+        ),
+        ("Blocks", blocks),
+    ]);
+    vec![("Schematic", compound(tags))]
+}
+
+fn gzip(tags: Vec<(&str, nbt::Value)>) -> Vec<u8> {
+    let mut blob = nbt::Blob::new();
+    for (name, value) in tags {
+        blob.insert(name, value).unwrap();
+    }
+    let mut data = Vec::new();
+    blob.to_gzip_writer(&mut data).unwrap();
+    data
+}
+
+fn check_fixture(world: &World, offset: BlockPos) {
+    assert_eq!(world.min(), offset);
+    assert_eq!(world.size(), (3, 2, 2));
+    let state = |x, y, z| world.get_block_state(offset.offset(x, y, z)).to_string();
+    assert_eq!(state(0, 0, 0), "minecraft:stone");
+    assert_eq!(state(2, 0, 1), "minecraft:stone");
+    assert_eq!(state(1, 1, 0), "minecraft:air");
+    assert_eq!(state(0, 1, 0), CHEST);
+    assert_eq!(state(2, 1, 1), SIGN);
+
+    assert_eq!(world.block_entities().count(), 2);
+    for (id, pos, data) in entity_data() {
+        let pos = offset.offset(pos[0], pos[1], pos[2]);
+        let entity = world.get_block_entity(pos).unwrap();
+        assert_eq!(entity.id, id);
+        assert_eq!(entity.data, data);
+    }
+}
+
+#[test]
+fn version_1() {
+    let offset = BlockPos::new(-4, 2, 7);
+    let world = World::from_bytes(&gzip(v1(offset))).unwrap();
+    check_fixture(&world, offset);
+}
+
+#[test]
+fn version_2() {
+    let offset = BlockPos::new(10, -3, 0);
+    let world = World::from_bytes(&gzip(v2(offset))).unwrap();
+    check_fixture(&world, offset);
+}
+
+#[test]
+fn version_3() {
+    let offset = BlockPos::new(-1, -1, -1);
+    let world = World::from_bytes(&gzip(v3(offset))).unwrap();
+    check_fixture(&world, offset);
+}
+
+#[test]
+fn loads_from_disk() {
+    let path =
+        std::env::temp_dir().join(format!("redstone_schem_load_{}.schem", std::process::id()));
+    std::fs::write(&path, gzip(v2(BlockPos::default()))).unwrap();
+    let world = World::load_schematic(path.to_str().unwrap());
+    let _ = std::fs::remove_file(&path);
+    check_fixture(&world.unwrap(), BlockPos::default());
+
+    let missing = World::load_schematic(path.to_str().unwrap());
+    assert!(matches!(missing, Err(Error::Io(_))));
+}
+
+/// The version 2 fixture with `name` left out or replaced by `value`
+fn v2_with(name: &'static str, value: Option<nbt::Value>) -> Vec<u8> {
+    let mut tags = v2(BlockPos::default());
+    tags.retain(|&(tag, _)| tag != name);
+    if let Some(value) = value {
+        tags.push((name, value));
+    }
+    gzip(tags)
+}
+
+#[test]
+fn missing_tags() {
+    for tag in ["Version", "Palette", "BlockData", "Width"] {
+        let result = World::from_bytes(&v2_with(tag, None));
+        assert!(
+            matches!(result, Err(Error::MissingTag(missing)) if missing == tag),
+            "{}",
+            tag
+        );
+    }
+    let mut tags = v3(BlockPos::default());
+    if let nbt::Value::Compound(schematic) = &mut tags[0].1 {
+        schematic.remove("Blocks");
+    }
+    assert!(matches!(
+        World::from_bytes(&gzip(tags)),
+        Err(Error::MissingTag("Blocks"))
+    ));
+
+    // Block entities need an id and a position
+    let entity = compound(vec![("Pos", nbt::Value::IntArray(vec![0, 0, 0]))]);
+    let data = v2_with("BlockEntities", Some(nbt::Value::List(vec![entity])));
+    assert!(matches!(
+        World::from_bytes(&data),
+        Err(Error::MissingTag("Id"))
+    ));
+}
+
+#[test]
+fn bad_data() {
+    let result = World::from_bytes(&v2_with("Version", Some(nbt::Value::Int(4))));
+    assert!(matches!(result, Err(Error::UnsupportedVersion(4))));
+
+    // One block short
+    let mut short = match block_data() {
+        nbt::Value::ByteArray(data) => data,
+        _ => unreachable!(),
+    };
+    short.pop();
+    let data = v2_with("BlockData", Some(nbt::Value::ByteArray(short.clone())));
+    assert!(matches!(
+        World::from_bytes(&data),
+        Err(Error::InvalidSchematic(_))
+    ));
+
+    // Ending halfway through a varint
+    short.push(-128);
+    let data = v2_with("BlockData", Some(nbt::Value::ByteArray(short)));
+    assert!(matches!(
+        World::from_bytes(&data),
+        Err(Error::InvalidSchematic(_))
+    ));
+
+    // An index that isn't in the palette
+    let mut undefined = Vec::new();
+    for _ in 0..12 {
+        varint::encode(9, &mut undefined);
+    }
+    let data = v2_with("BlockData", Some(nbt::Value::ByteArray(undefined)));
+    assert!(matches!(
+        World::from_bytes(&data),
+        Err(Error::InvalidSchematic(_))
+    ));
+
+    // A block entity outside of the schematic
+    let entity = compound(vec![
+        ("Id", string("minecraft:chest")),
+        ("Pos", nbt::Value::IntArray(vec![3, 0, 0])),
+    ]);
+    let data = v2_with("BlockEntities", Some(nbt::Value::List(vec![entity])));
+    assert!(matches!(
+        World::from_bytes(&data),
+        Err(Error::InvalidSchematic(_))
+    ));
+
+    // A file that was cut off
+    let data = gzip(v2(BlockPos::default()));
+    assert!(World::from_bytes(&data[..data.len() / 2]).is_err());
+    assert!(World::from_bytes(&[]).is_err());
+}
@@ -0,0 +1,69 @@
+use redstone_schem::error::Error;
+use redstone_schem::world::{BlockPos, World};
+use std::fs;
+
+/// A row of stone and glass that takes one command per block
+fn striped(length: i32) -> World {
+    let mut world = World::new(length as usize, 1, 1);
+    let stone = world.add_block("minecraft:stone");
+    let glass = world.add_block("minecraft:glass");
+    for x in 0..length {
+        world.set_block(BlockPos::new(x, 0, 0), [stone, glass][x as usize % 2]);
+    }
+    world
+}
+
+#[test]
+fn later_parts_use_absolute_coordinates() {
+    let world = striped(3);
+    let parts = world
+        .mcfunction_data("test:build", BlockPos::new(100, 64, -20), 2)
+        .unwrap();
+    assert_eq!(
+        parts,
+        [
+            "setblock 100 64 -20 minecraft:stone\nschedule function test:build_1 1t\n",
+            "setblock 101 64 -20 minecraft:glass\nschedule function test:build_2 1t\n",
+            "setblock 102 64 -20 minecraft:stone\n",
+        ]
+    );
+}
+
+#[test]
+fn functions_need_room_to_schedule() {
+    let world = striped(3);
+    for max_commands in [0, 1] {
+        assert!(matches!(
+            world.mcfunction_data("test:build", BlockPos::default(), max_commands),
+            Err(Error::TooFewCommands(max)) if max == max_commands
+        ));
+    }
+}
+
+#[test]
+fn stale_parts_are_removed() {
+    let dir =
+        std::env::temp_dir().join(format!("redstone_schem_mcfunction_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let dir_name = dir.to_str().unwrap();
+
+    striped(4)
+        .save_mcfunction(dir_name, "test:build", BlockPos::default(), 2)
+        .unwrap();
+    for i in 0..4 {
+        assert!(dir.join(format!("build_{}.mcfunction", i)).exists());
+    }
+
+    striped(2)
+        .save_mcfunction(dir_name, "test:build", BlockPos::default(), 2)
+        .unwrap();
+    let mut files = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect::<Vec<_>>();
+    files.sort();
+    let last = fs::read_to_string(dir.join("build_1.mcfunction")).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(files, ["build_0.mcfunction", "build_1.mcfunction"]);
+    assert_eq!(last, "setblock 1 0 0 minecraft:glass\n");
+}
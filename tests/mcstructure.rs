@@ -0,0 +1,42 @@
+use redstone_schem::world::{BlockPos, World};
+
+/// A string tag value as bedrock's little endian NBT stores it
+fn nbt_string(value: &str) -> Vec<u8> {
+    let mut out = (value.len() as u16).to_le_bytes().to_vec();
+    out.extend_from_slice(value.as_bytes());
+    out
+}
+
+fn contains(data: &[u8], needle: &[u8]) -> bool {
+    data.windows(needle.len()).any(|window| window == needle)
+}
+
+#[test]
+fn wall_torches_face_the_block_they_hang_on() {
+    for (java, bedrock) in [
+        ("east", "west"),
+        ("west", "east"),
+        ("north", "south"),
+        ("south", "north"),
+    ] {
+        let mut world = World::new(1, 1, 1);
+        let torch = world.add_block(&format!("minecraft:redstone_wall_torch[facing={}]", java));
+        world.set_block(BlockPos::new(0, 0, 0), torch);
+        let data = world.mcstructure_data().unwrap();
+        assert!(contains(&data, &nbt_string(bedrock)), "facing={}", java);
+        assert!(!contains(&data, &nbt_string(java)), "facing={}", java);
+    }
+}
+
+#[test]
+fn standing_torches_face_up() {
+    let mut world = World::new(1, 1, 1);
+    let torch = world.add_block("minecraft:redstone_torch[lit=false]");
+    world.set_block(BlockPos::new(0, 0, 0), torch);
+    let data = world.mcstructure_data().unwrap();
+    assert!(contains(&data, &nbt_string("top")));
+    assert!(contains(
+        &data,
+        &nbt_string("minecraft:unlit_redstone_torch")
+    ));
+}
@@ -0,0 +1,190 @@
+use redstone_schem::error::Error;
+use redstone_schem::region::Region;
+use redstone_schem::router::RouteOptions;
+use redstone_schem::world::{BlockDirection, BlockPos, World};
+
+/// Every block other than air, in a fixed order
+fn snapshot(world: &World) -> Vec<((i32, i32, i32), u16)> {
+    let mut blocks = world
+        .blocks()
+        .filter(|&(_, block)| block != 0)
+        .map(|(pos, block)| ((pos.x, pos.y, pos.z), block))
+        .collect::<Vec<_>>();
+    blocks.sort();
+    blocks
+}
+
+fn wires(world: &World) -> Vec<BlockPos> {
+    world
+        .blocks()
+        .filter(|&(pos, _)| world.get_block_state(pos).id == "redstone_wire")
+        .map(|(pos, _)| pos)
+        .collect()
+}
+
+#[test]
+fn routes_on_open_ground() {
+    let mut world = World::default();
+    let stone = world.add_block("minecraft:stone");
+    let (from, to) = (BlockPos::new(0, 1, 0), BlockPos::new(10, 1, 3));
+    let routed = world
+        .route(stone, from, to, RouteOptions::default())
+        .unwrap();
+    assert_eq!(routed.output, to);
+    assert_eq!((routed.delay, routed.ss, routed.inverted), (0, 2, false));
+    // The shortest route has one wire per step
+    assert_eq!(wires(&world).len(), 14);
+    for pos in [from, to] {
+        assert_eq!(world.get_block_state(pos).id, "redstone_wire");
+        assert_eq!(world.get_block(pos.offset(0, -1, 0)), stone);
+    }
+}
+
+#[test]
+fn long_routes_get_repeaters() {
+    let mut world = World::default();
+    let stone = world.add_block("minecraft:stone");
+    let (from, to) = (BlockPos::new(0, 1, 0), BlockPos::new(40, 1, 0));
+    let routed = world
+        .route(stone, from, to, RouteOptions::default())
+        .unwrap();
+    assert_eq!(routed.delay, 2);
+    assert!(routed.ss > 0);
+}
+
+#[test]
+fn keeps_clear_of_components() {
+    let mut world = World::default();
+    let stone = world.add_block("minecraft:stone");
+    let lamp = world.add_block("minecraft:redstone_lamp");
+    let lamp_pos = BlockPos::new(5, 1, 0);
+    world.set_block(lamp_pos, lamp);
+    world
+        .route(
+            stone,
+            BlockPos::new(0, 1, 0),
+            BlockPos::new(10, 1, 0),
+            RouteOptions::default(),
+        )
+        .unwrap();
+    assert_eq!(world.get_block(lamp_pos), lamp);
+    for wire in wires(&world) {
+        let d = wire - lamp_pos;
+        assert!(
+            d.x.abs() + d.z.abs() > 1 || d.y.abs() > 1,
+            "wire at {:?} touches the lamp",
+            wire
+        );
+    }
+}
+
+#[test]
+fn fails_when_boxed_in() {
+    let mut world = World::default();
+    let stone = world.add_block("minecraft:stone");
+    let from = BlockPos::new(0, 1, 0);
+    world.fill(
+        Region::new(from.offset(-1, -2, -1), from.offset(1, 1, 1)),
+        stone,
+    );
+    world.set_block(from, 0);
+    world.set_block(from.offset(0, -1, 0), 0);
+    let before = snapshot(&world);
+
+    let to = BlockPos::new(10, 1, 0);
+    let result = world.route(stone, from, to, RouteOptions::default());
+    assert!(matches!(result, Err(Error::NoRoute { from: f, to: t }) if f == from && t == to));
+    assert_eq!(snapshot(&world), before);
+
+    // Opening one side is enough
+    let side = from.offset_dir(BlockDirection::East, 1);
+    world.set_block(side, 0);
+    world.set_block(side.offset(0, -1, 0), 0);
+    assert!(world
+        .route(stone, from, to, RouteOptions::default())
+        .is_ok());
+}
+
+#[test]
+fn gives_up_after_max_nodes() {
+    let mut world = World::default();
+    let stone = world.add_block("minecraft:stone");
+    let (from, to) = (BlockPos::new(0, 1, 0), BlockPos::new(30, 1, 0));
+    let options = RouteOptions {
+        max_nodes: 10,
+        ..Default::default()
+    };
+    assert!(matches!(
+        world.route(stone, from, to, options),
+        Err(Error::NoRoute { .. })
+    ));
+    assert!(wires(&world).is_empty());
+    assert!(world
+        .route(stone, from, to, RouteOptions::default())
+        .is_ok());
+}
+
+/// The delay of every repeater in the world added up
+fn total_delay(world: &World) -> u32 {
+    world
+        .blocks()
+        .map(|(pos, _)| world.get_block_state(pos))
+        .filter(|state| state.id == "repeater")
+        .map(|state| state.property("delay").map_or(1, |d| d.parse().unwrap()))
+        .sum()
+}
+
+#[test]
+fn crossed_bits_have_equal_delays() {
+    let mut world = World::default();
+    let stone = world.add_block("minecraft:stone");
+    let from = [BlockPos::new(0, 1, 0), BlockPos::new(0, 1, 2)];
+    let to = [BlockPos::new(36, 1, 2), BlockPos::new(36, 1, 0)];
+    let routes = world
+        .route_bus(stone, &from, &to, RouteOptions::default())
+        .unwrap();
+    assert_eq!(routes.len(), 2);
+    assert_eq!(routes[0].delay, routes[1].delay);
+    assert!(routes[0].delay > 0);
+    for (route, &to) in routes.iter().zip(&to) {
+        assert_eq!(route.output, to);
+        assert!(route.ss > 0);
+    }
+    // Every bit's repeaters add up to the bus delay
+    assert_eq!(total_delay(&world), routes[0].delay * 2);
+}
+
+#[test]
+fn failed_buses_leave_the_world_alone() {
+    let mut world = World::default();
+    let stone = world.add_block("minecraft:stone");
+    let boxed = BlockPos::new(0, 1, 8);
+    world.fill(
+        Region::new(boxed.offset(-1, -2, -1), boxed.offset(1, 1, 1)),
+        stone,
+    );
+    world.set_block(boxed, 0);
+    world.set_block(boxed.offset(0, -1, 0), 0);
+    let before = snapshot(&world);
+
+    let from = [BlockPos::new(0, 1, 0), boxed];
+    let to = [BlockPos::new(20, 1, 0), BlockPos::new(20, 1, 8)];
+    let result = world.route_bus(stone, &from, &to, RouteOptions::default());
+    assert!(matches!(result, Err(Error::NoRoute { from, .. }) if from == boxed));
+    assert_eq!(snapshot(&world), before);
+}
+
+#[test]
+fn short_bits_that_cant_slow_down() {
+    let mut world = World::default();
+    let stone = world.add_block("minecraft:stone");
+    // The second bit is too short to hold a repeater
+    let from = [BlockPos::new(0, 1, 0), BlockPos::new(0, 1, 10)];
+    let to = [BlockPos::new(40, 1, 0), BlockPos::new(1, 1, 10)];
+    let result = world.route_bus(stone, &from, &to, RouteOptions::default());
+    assert!(matches!(
+        result,
+        Err(Error::DelayMismatch { missing, .. }) if missing > 0
+    ));
+    assert!(snapshot(&world).is_empty());
+}
@@ -0,0 +1,106 @@
+use redstone_schem::block_state::BlockState;
+use redstone_schem::transform::Axis;
+
+const STATES: [&str; 14] = [
+    "minecraft:repeater[delay=2,facing=east]",
+    "minecraft:redstone_wire[east=side,north=up,power=3,south=none,west=side]",
+    "minecraft:oak_log[axis=x]",
+    "minecraft:oak_sign[rotation=3]",
+    "minecraft:oak_stairs[facing=north,half=top,shape=outer_left]",
+    "minecraft:oak_door[facing=west,half=lower,hinge=right]",
+    "minecraft:smooth_stone_slab[type=bottom]",
+    "minecraft:lever[face=ceiling,facing=south]",
+    "minecraft:rail[shape=north_east]",
+    "minecraft:rail[shape=south_west]",
+    "minecraft:rail[shape=east_west]",
+    "minecraft:powered_rail[powered=false,shape=ascending_north]",
+    "minecraft:chest[facing=north,type=left]",
+    "minecraft:chest[facing=east,type=single]",
+];
+
+fn state(name: &str) -> BlockState {
+    name.parse().unwrap()
+}
+
+#[test]
+fn four_turns_are_the_identity() {
+    for name in STATES {
+        let original = state(name);
+        let mut rotated = original.clone();
+        for _ in 0..4 {
+            rotated = rotated.rotate(1);
+        }
+        assert_eq!(rotated, original, "{}", name);
+        assert_eq!(original.rotate(4), original, "{}", name);
+        assert_eq!(original.rotate(1).rotate(-1), original, "{}", name);
+    }
+}
+
+#[test]
+fn mirroring_twice_is_the_identity() {
+    for name in STATES {
+        let original = state(name);
+        for axis in [Axis::X, Axis::Y, Axis::Z] {
+            assert_eq!(original.mirror(axis).mirror(axis), original, "{}", name);
+        }
+    }
+}
+
+#[test]
+fn rails_keep_valid_shapes() {
+    let shape = |state: BlockState| state.property("shape").unwrap().to_owned();
+    assert_eq!(
+        shape(state("minecraft:rail[shape=north_east]").rotate(1)),
+        "south_east"
+    );
+    assert_eq!(
+        shape(state("minecraft:rail[shape=north_east]").rotate(2)),
+        "south_west"
+    );
+    assert_eq!(
+        shape(state("minecraft:rail[shape=north_south]").rotate(2)),
+        "north_south"
+    );
+    assert_eq!(
+        shape(state("minecraft:rail[shape=north_south]").rotate(1)),
+        "east_west"
+    );
+    assert_eq!(
+        shape(state("minecraft:rail[shape=ascending_east]").rotate(1)),
+        "ascending_south"
+    );
+    assert_eq!(
+        shape(state("minecraft:rail[shape=north_east]").mirror(Axis::X)),
+        "north_west"
+    );
+    assert_eq!(
+        shape(state("minecraft:rail[shape=north_east]").mirror(Axis::Z)),
+        "south_east"
+    );
+    assert_eq!(
+        shape(state("minecraft:rail[shape=ascending_north]").mirror(Axis::Z)),
+        "ascending_south"
+    );
+    assert_eq!(
+        shape(state("minecraft:oak_stairs[facing=north,shape=inner_left]").rotate(1)),
+        "inner_left"
+    );
+}
+
+#[test]
+fn mirrored_double_chests_swap_halves() {
+    let chest = state("minecraft:chest[facing=north,type=left]");
+    assert_eq!(
+        chest.mirror(Axis::X),
+        state("minecraft:chest[facing=north,type=right]")
+    );
+    assert_eq!(
+        chest.mirror(Axis::Z),
+        state("minecraft:chest[facing=south,type=right]")
+    );
+    assert_eq!(chest.mirror(Axis::Y), chest);
+    assert_eq!(
+        chest.rotate(1),
+        state("minecraft:chest[facing=east,type=left]")
+    );
+}
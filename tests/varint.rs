@@ -0,0 +1,122 @@
+use redstone_schem::varint;
+use redstone_schem::world::{BlockPos, ExportArea, SchematicVersion, World};
+
+/// Small xorshift generator so the tests are reproducible without extra dependencies
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+#[test]
+fn encoded_lengths() {
+    let len = |value| {
+        let mut out = Vec::new();
+        varint::encode(value, &mut out);
+        out.len()
+    };
+    assert_eq!(len(0), 1);
+    assert_eq!(len(127), 1);
+    assert_eq!(len(128), 2);
+    assert_eq!(len(16383), 2);
+    assert_eq!(len(16384), 3);
+    assert_eq!(len(u16::MAX as u32), 3);
+    assert_eq!(len(u32::MAX), 5);
+
+    let mut out = Vec::new();
+    varint::encode(300, &mut out);
+    assert_eq!(out, [0b1010_1100u8 as i8, 0b0000_0010]);
+}
+
+#[test]
+fn round_trip_every_u16() {
+    let mut out = Vec::new();
+    for value in 0..=u16::MAX as u32 {
+        varint::encode(value, &mut out);
+    }
+    let decoded = varint::decode(&out).unwrap();
+    assert_eq!(decoded, (0..=u16::MAX as u32).collect::<Vec<_>>());
+}
+
+#[test]
+fn round_trip_random() {
+    let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+    for _ in 0..100 {
+        let values = (0..rng.next() % 1000)
+            .map(|_| (rng.next() >> (rng.next() % 64)) as u32)
+            .collect::<Vec<_>>();
+        let mut out = Vec::new();
+        for &value in &values {
+            varint::encode(value, &mut out);
+        }
+        assert_eq!(varint::decode(&out).unwrap(), values);
+    }
+}
+
+#[test]
+fn rejects_malformed() {
+    // Ends in the middle of a value
+    assert!(varint::decode(&[0b1000_0000u8 as i8]).is_err());
+    // More bits than fit in 32
+    assert!(varint::decode(&[-1, -1, -1, -1, 0b0001_0000]).is_err());
+    assert!(varint::decode(&[-1, -1, -1, -1, -1, 0]).is_err());
+    assert_eq!(
+        varint::decode(&[-1, -1, -1, -1, 0b1111]).unwrap(),
+        [u32::MAX]
+    );
+}
+
+#[test]
+fn schematic_round_trip_large_palettes() {
+    let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+    for &palette_len in &[
+        2,
+        127,
+        128,
+        129,
+        300,
+        16383,
+        16384,
+        16385,
+        u16::MAX as usize,
+    ] {
+        let (sx, sy, sz) = (64, (palette_len / 512).max(1) * 2, 8);
+        let mut world = World::new(sx, sy, sz);
+        let blocks = (1..palette_len)
+            .map(|i| world.add_block(&format!("minecraft:test_{}", i)))
+            .collect::<Vec<_>>();
+
+        let mut expected = Vec::new();
+        for y in 0..sy {
+            for z in 0..sz {
+                for x in 0..sx {
+                    // Make sure the largest id is always used at least once
+                    let block = if (x, y, z) == (0, 0, 0) {
+                        *blocks.last().unwrap()
+                    } else {
+                        blocks[rng.next() as usize % blocks.len()]
+                    };
+                    let pos = BlockPos::new(x as i32, y as i32, z as i32);
+                    world.set_block(pos, block);
+                    expected.push((pos, block));
+                }
+            }
+        }
+        let names = world.palette().to_vec();
+
+        for &version in &[SchematicVersion::V2, SchematicVersion::V3] {
+            let data = world.data(version, ExportArea::Bounds).unwrap();
+            let loaded = World::from_bytes(&data).unwrap();
+            let loaded_names = loaded.palette();
+            for &(pos, block) in &expected {
+                let name = &loaded_names[loaded.get_block(pos) as usize];
+                assert_eq!(name, &names[block as usize]);
+            }
+        }
+    }
+}
@@ -0,0 +1,214 @@
+use redstone_schem::block_entity::{BlockEntity, ItemStack};
+use redstone_schem::block_state::BlockState;
+use redstone_schem::error::Error;
+use redstone_schem::region::Region;
+use redstone_schem::world::{BlockPos, PasteOptions, World};
+
+#[test]
+fn full_palettes_are_reported() {
+    let mut world = World::default();
+    // Air already takes the first id
+    for i in 1..=u16::MAX as u32 {
+        let state = BlockState::new("minecraft:note_block").with("id", i);
+        assert_eq!(world.try_add_block_state(&state).unwrap(), i as u16);
+    }
+    let state = BlockState::new("minecraft:note_block").with("id", "full");
+    assert!(matches!(
+        world.try_add_block_state(&state),
+        Err(Error::PaletteFull)
+    ));
+    assert!(matches!(
+        world.try_add_block("minecraft:stone"),
+        Err(Error::PaletteFull)
+    ));
+    // States that are already there can still be looked up
+    assert_eq!(world.add_block("minecraft:air"), 0);
+}
+
+fn non_air(world: &World) -> usize {
+    world.blocks().filter(|&(_, block)| block != 0).count()
+}
+
+#[test]
+fn sections_fill_and_empty() {
+    let mut world = World::default();
+    let stone = world.add_block("minecraft:stone");
+    let glass = world.add_block("minecraft:glass");
+    // Covers one whole section and parts of the ones around it
+    let region = Region::new(BlockPos::new(-3, 0, -3), BlockPos::new(16, 15, 20));
+    world.fill(region, stone);
+    assert_eq!(non_air(&world), 20 * 16 * 24);
+    assert_eq!(world.size(), (20, 16, 24));
+    assert_eq!(world.get_block(BlockPos::new(7, 7, 7)), stone);
+    assert_eq!(world.get_block(BlockPos::new(17, 7, 7)), 0);
+
+    // Writing into a uniform section only changes that block
+    world.set_block(BlockPos::new(7, 7, 7), glass);
+    assert_eq!(world.get_block(BlockPos::new(7, 7, 7)), glass);
+    assert_eq!(world.get_block(BlockPos::new(7, 7, 8)), stone);
+    world.set_block(BlockPos::new(7, 7, 7), stone);
+
+    // A section filled block by block reads the same as one filled at once
+    for y in 16..32 {
+        for z in 0..16 {
+            for x in 0..16 {
+                world.set_block(BlockPos::new(x, y, z), stone);
+            }
+        }
+    }
+    assert_eq!(non_air(&world), 20 * 16 * 24 + 4096);
+
+    world.translate(5, 3, -7);
+    assert_eq!(non_air(&world), 20 * 16 * 24 + 4096);
+    assert_eq!(world.get_block(BlockPos::new(5, 3, -7)), stone);
+    assert_eq!(world.get_block(BlockPos::new(5, 35, -7)), 0);
+
+    // Clearing everything leaves no blocks behind, even though the bounds stay
+    let everything = Region::new(world.min(), world.min().offset(19, 31, 23));
+    world.fill(everything, 0);
+    assert_eq!(non_air(&world), 0);
+    assert_eq!(world.non_air_region(), None);
+    world.set_block(BlockPos::new(40, 40, 40), glass);
+    world.set_block(BlockPos::new(40, 40, 40), 0);
+    assert_eq!(non_air(&world), 0);
+}
+
+/// Two blocks of stone with air between them, and a chest with a sign on top of it
+fn paste_source() -> World {
+    let mut world = World::new(3, 2, 1);
+    let stone = world.add_block("minecraft:stone");
+    let chest = world.add_block("minecraft:chest[facing=north,type=single]");
+    let sign = world.add_block("minecraft:oak_sign[rotation=0,waterlogged=false]");
+    world.set_block(BlockPos::new(0, 0, 0), stone);
+    world.set_block(BlockPos::new(2, 0, 0), stone);
+    world.set_block(BlockPos::new(0, 1, 0), chest);
+    world.set_block(BlockPos::new(1, 1, 0), sign);
+    world.set_block_entity(
+        BlockPos::new(0, 1, 0),
+        BlockEntity::container("chest", &[ItemStack::new("redstone", 5)]),
+    );
+    world.set_block_entity(
+        BlockPos::new(1, 1, 0),
+        BlockEntity::new("sign").with("Text1", nbt::Value::String("{\"text\":\"hi\"}".into())),
+    );
+    world
+}
+
+/// A row of glass under a barrel, with a palette that has nothing in common with the source's
+/// past air
+fn paste_destination() -> World {
+    let mut world = World::new(3, 2, 1);
+    let glass = world.add_block("minecraft:glass");
+    for x in 0..3 {
+        world.set_block(BlockPos::new(x, 0, 0), glass);
+    }
+    world.set_barrel(BlockPos::new(1, 1, 0), 7);
+    world
+}
+
+fn state_at(world: &World, x: i32, y: i32, z: i32) -> String {
+    world.get_block_state(BlockPos::new(x, y, z)).to_string()
+}
+
+#[test]
+fn paste_remaps_palettes_and_copies_block_entities() {
+    let source = paste_source();
+    let mut world = paste_destination();
+    world.paste(&source, BlockPos::new(10, 0, 0), PasteOptions::default());
+    assert_eq!(state_at(&world, 10, 0, 0), "minecraft:stone");
+    assert_eq!(state_at(&world, 11, 0, 0), "minecraft:air");
+    assert_eq!(
+        state_at(&world, 10, 1, 0),
+        "minecraft:chest[facing=north,type=single]"
+    );
+    assert_eq!(
+        state_at(&world, 11, 1, 0),
+        "minecraft:oak_sign[rotation=0,waterlogged=false]"
+    );
+    // The destination's own blocks keep their ids
+    assert_eq!(state_at(&world, 0, 0, 0), "minecraft:glass");
+    assert_eq!(world.palette().len(), 6);
+
+    for (x, y) in [(0, 1), (1, 1)] {
+        let copy = world.get_block_entity(BlockPos::new(10 + x, y, 0));
+        assert_eq!(copy, source.get_block_entity(BlockPos::new(x, y, 0)));
+        assert!(copy.is_some());
+    }
+    assert_eq!(world.block_entities().count(), 3);
+}
+
+#[test]
+fn paste_replaces_everything_by_default() {
+    let mut world = paste_destination();
+    world.paste(
+        &paste_source(),
+        BlockPos::default(),
+        PasteOptions::default(),
+    );
+    assert_eq!(state_at(&world, 1, 0, 0), "minecraft:air");
+    assert_eq!(
+        state_at(&world, 1, 1, 0),
+        "minecraft:oak_sign[rotation=0,waterlogged=false]"
+    );
+    // The barrel's contents don't stay behind under the sign
+    let sign = world.get_block_entity(BlockPos::new(1, 1, 0)).unwrap();
+    assert_eq!(sign.id, "minecraft:sign");
+    assert!(!sign.data.contains_key("Items"));
+
+    // Air removes block entities too
+    let mut world = paste_destination();
+    world.paste(
+        &World::new(3, 2, 1),
+        BlockPos::default(),
+        PasteOptions::default(),
+    );
+    assert_eq!(state_at(&world, 1, 1, 0), "minecraft:air");
+    assert_eq!(world.block_entities().count(), 0);
+}
+
+#[test]
+fn paste_can_skip_air() {
+    let mut world = paste_destination();
+    let options = PasteOptions {
+        skip_air: true,
+        ..Default::default()
+    };
+    world.paste(&paste_source(), BlockPos::default(), options);
+    assert_eq!(state_at(&world, 0, 0, 0), "minecraft:stone");
+    assert_eq!(state_at(&world, 1, 0, 0), "minecraft:glass");
+    assert_eq!(state_at(&world, 2, 0, 0), "minecraft:stone");
+    assert_eq!(state_at(&world, 2, 1, 0), "minecraft:air");
+
+    // Air doesn't clear block entities when it's skipped
+    let mut world = paste_destination();
+    world.paste(&World::new(3, 2, 1), BlockPos::default(), options);
+    assert_eq!(state_at(&world, 1, 1, 0), "minecraft:barrel");
+    assert_eq!(world.block_entities().count(), 1);
+}
+
+#[test]
+fn paste_can_only_replace_air() {
+    let mut world = paste_destination();
+    let options = PasteOptions {
+        only_replace_air: true,
+        ..Default::default()
+    };
+    world.paste(&paste_source(), BlockPos::default(), options);
+    // The bottom row and the barrel are kept, along with the barrel's items
+    for x in 0..3 {
+        assert_eq!(state_at(&world, x, 0, 0), "minecraft:glass");
+    }
+    assert_eq!(state_at(&world, 1, 1, 0), "minecraft:barrel");
+    let barrel = world.get_block_entity(BlockPos::new(1, 1, 0)).unwrap();
+    assert_eq!(barrel.id, "minecraft:barrel");
+    // Only the chest lands in air
+    assert_eq!(
+        state_at(&world, 0, 1, 0),
+        "minecraft:chest[facing=north,type=single]"
+    );
+    assert_eq!(
+        world.get_block_entity(BlockPos::new(0, 1, 0)).unwrap().id,
+        "minecraft:chest"
+    );
+    assert_eq!(world.block_entities().count(), 2);
+}